@@ -0,0 +1,70 @@
+//! How fast the scanner gets through a few megabytes of Lox.
+//!
+//! Run it with `cargo bench --bench scanner`. It doesn't need any extra
+//! crates, so it just times a handful of runs and reports the best one.
+
+use jlox::scanner::Scanner;
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+/// A chunk of typical Lox code, with a bit of everything the scanner handles.
+const SAMPLE: &str = r#"
+// a class, some methods and a closure
+class Counter < Base {
+  init(start) {
+    this.count = start;
+    this.label = "counter número uno";
+  }
+
+  increment(by) {
+    this.count = this.count + by * 2.5 - 1;
+    return this.count >= 100 and this.count != 42 or !false;
+  }
+}
+
+fun makeAdder(n) {
+  fun add(x) { return x + n; }
+  return add;
+}
+
+var total = 0;
+for (var i = 0; i < 1000; i = i + 1) {
+  if (i / 3 <= 7) total = total + makeAdder(i)(1); else print "skip";
+}
+"#;
+
+const RUNS: usize = 10;
+
+/// The quickest of `RUNS` runs of `f`, and what it handed back.
+fn best<T, F: FnMut() -> T>(mut f: F) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = black_box(f());
+        best = best.min(start.elapsed());
+        result = Some(value);
+    }
+    (best, result.unwrap())
+}
+
+fn main() {
+    for &megabytes in &[1, 4, 16] {
+        let copies = megabytes * 1024 * 1024 / SAMPLE.len() + 1;
+        let source = SAMPLE.repeat(copies);
+
+        let (time, (tokens, errors)) = best(|| Scanner::new(black_box(&source)).scan_tokens());
+        assert!(errors.is_empty());
+
+        let mb = source.len() as f64 / (1024.0 * 1024.0);
+        println!(
+            "{:>6.1} MB, {:>9} tokens: {:>8.2?} ({:.1} MB/s)",
+            mb,
+            tokens.len(),
+            time,
+            mb / time.as_secs_f64()
+        );
+    }
+}
@@ -1,3 +1,4 @@
 pub mod expr;
 pub mod printer;
+pub mod span;
 pub mod stmt;
@@ -1,31 +1,65 @@
 use crate::literal::Value;
 use crate::token::Token;
+use std::cell::Cell;
 #[derive(Debug)]
 pub enum Expr {
-    Assign(Token, Box<Expr>),
+    Assign(Token, Box<Expr>, Cell<Option<usize>>),
     Binary(Box<Expr>, Token, Box<Expr>),
+    Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
     Grouping(Box<Expr>),
-    Literal(Value),
+    Index(Box<Expr>, Token, Box<Expr>),
+    Interpolation(Token, Vec<Expr>),
+    List(Token, Vec<Expr>, Token),
+    Literal(Token, Value),
+    Logical(Box<Expr>, Token, Box<Expr>),
+    Map(Token, Vec<Expr>, Vec<Expr>, Token),
+    Set(Box<Expr>, Token, Box<Expr>),
+    SetIndex(Box<Expr>, Token, Box<Expr>, Box<Expr>),
+    Super(Token, Token, Cell<Option<usize>>),
+    This(Token, Cell<Option<usize>>),
     Unary(Token, Box<Expr>),
-    Variable(Token),
+    Variable(Token, Cell<Option<usize>>),
 }
 impl Expr {
     pub fn accept<R, V: Visitor<R>>(&self, visitor: &mut V) -> R {
         match self {
-            Expr::Assign(a0, a1) => visitor.visit_assign_expr(a0, a1),
+            Expr::Assign(a0, a1, a2) => visitor.visit_assign_expr(a0, a1, a2),
             Expr::Binary(a0, a1, a2) => visitor.visit_binary_expr(a0, a1, a2),
+            Expr::Call(a0, a1, a2) => visitor.visit_call_expr(a0, a1, a2),
+            Expr::Get(a0, a1) => visitor.visit_get_expr(a0, a1),
             Expr::Grouping(a0) => visitor.visit_grouping_expr(a0),
-            Expr::Literal(a0) => visitor.visit_literal_expr(a0),
+            Expr::Index(a0, a1, a2) => visitor.visit_index_expr(a0, a1, a2),
+            Expr::Interpolation(a0, a1) => visitor.visit_interpolation_expr(a0, a1),
+            Expr::List(a0, a1, a2) => visitor.visit_list_expr(a0, a1, a2),
+            Expr::Literal(a0, a1) => visitor.visit_literal_expr(a0, a1),
+            Expr::Logical(a0, a1, a2) => visitor.visit_logical_expr(a0, a1, a2),
+            Expr::Map(a0, a1, a2, a3) => visitor.visit_map_expr(a0, a1, a2, a3),
+            Expr::Set(a0, a1, a2) => visitor.visit_set_expr(a0, a1, a2),
+            Expr::SetIndex(a0, a1, a2, a3) => visitor.visit_setindex_expr(a0, a1, a2, a3),
+            Expr::Super(a0, a1, a2) => visitor.visit_super_expr(a0, a1, a2),
+            Expr::This(a0, a1) => visitor.visit_this_expr(a0, a1),
             Expr::Unary(a0, a1) => visitor.visit_unary_expr(a0, a1),
-            Expr::Variable(a0) => visitor.visit_variable_expr(a0),
+            Expr::Variable(a0, a1) => visitor.visit_variable_expr(a0, a1),
         }
     }
 }
 pub trait Visitor<R> {
-    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr) -> R;
+    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr, a2: &Cell<Option<usize>>) -> R;
     fn visit_binary_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> R;
+    fn visit_call_expr(&mut self, a0: &Expr, a1: &Token, a2: &[Expr]) -> R;
+    fn visit_get_expr(&mut self, a0: &Expr, a1: &Token) -> R;
     fn visit_grouping_expr(&mut self, a0: &Expr) -> R;
-    fn visit_literal_expr(&mut self, a0: &Value) -> R;
+    fn visit_index_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> R;
+    fn visit_interpolation_expr(&mut self, a0: &Token, a1: &[Expr]) -> R;
+    fn visit_list_expr(&mut self, a0: &Token, a1: &[Expr], a2: &Token) -> R;
+    fn visit_literal_expr(&mut self, a0: &Token, a1: &Value) -> R;
+    fn visit_logical_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> R;
+    fn visit_map_expr(&mut self, a0: &Token, a1: &[Expr], a2: &[Expr], a3: &Token) -> R;
+    fn visit_set_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> R;
+    fn visit_setindex_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr, a3: &Expr) -> R;
+    fn visit_super_expr(&mut self, a0: &Token, a1: &Token, a2: &Cell<Option<usize>>) -> R;
+    fn visit_this_expr(&mut self, a0: &Token, a1: &Cell<Option<usize>>) -> R;
     fn visit_unary_expr(&mut self, a0: &Token, a1: &Expr) -> R;
-    fn visit_variable_expr(&mut self, a0: &Token) -> R;
+    fn visit_variable_expr(&mut self, a0: &Token, a1: &Cell<Option<usize>>) -> R;
 }
@@ -1,32 +1,77 @@
 use super::expr;
-use crate::{literal::Value, token::Token};
+use crate::{
+    literal::{format_number, Value},
+    token::Token,
+};
 use expr::Expr;
+use std::cell::Cell;
 
 pub struct AstPrinter {}
 impl expr::Visitor<String> for AstPrinter {
     fn visit_binary_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> String {
-        self.parenthesize(&a1.lexeme, vec![a0, a2])
+        self.parenthesize(&a1.lexeme.to_string(), vec![a0, a2])
+    }
+    fn visit_call_expr(&mut self, a0: &Expr, _a1: &Token, a2: &[Expr]) -> String {
+        let mut exprs = vec![a0];
+        exprs.extend(a2);
+        self.parenthesize("call", exprs)
+    }
+    fn visit_get_expr(&mut self, a0: &Expr, a1: &Token) -> String {
+        self.parenthesize(&format!(".{}", a1.lexeme), vec![a0])
     }
     fn visit_grouping_expr(&mut self, a0: &Expr) -> String {
         self.parenthesize("group", vec![a0])
     }
-    fn visit_literal_expr(&mut self, a0: &Value) -> String {
-        match a0 {
+    fn visit_index_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr) -> String {
+        self.parenthesize("index", vec![a0, a2])
+    }
+    fn visit_interpolation_expr(&mut self, _a0: &Token, a1: &[Expr]) -> String {
+        self.parenthesize("interpolate", a1.iter().collect())
+    }
+    fn visit_list_expr(&mut self, _a0: &Token, a1: &[Expr], _a2: &Token) -> String {
+        self.parenthesize("list", a1.iter().collect())
+    }
+    fn visit_literal_expr(&mut self, _a0: &Token, a1: &Value) -> String {
+        match a1 {
             Value::Nil => String::from("nil"),
             Value::String(s) => s.to_string(),
-            Value::Number(n) => format!("{}", n),
+            Value::Number(n) => format_number(*n),
             Value::Bool(true) => String::from("true"),
             Value::Bool(false) => String::from("false"),
+            Value::Callable(c) => c.to_string(),
+            Value::Class(c) => c.to_string(),
+            Value::Instance(i) => i.borrow().to_string(),
+            Value::List(_) | Value::Map(_) | Value::Native(_) => a1.to_string(),
+            Value::Object(o) => o.to_string(),
         }
     }
+    fn visit_map_expr(&mut self, _a0: &Token, a1: &[Expr], a2: &[Expr], _a3: &Token) -> String {
+        let entries = a1.iter().zip(a2).flat_map(|(key, value)| vec![key, value]);
+        self.parenthesize("map", entries.collect())
+    }
+    fn visit_logical_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> String {
+        self.parenthesize(&a1.lexeme.to_string(), vec![a0, a2])
+    }
+    fn visit_set_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> String {
+        self.parenthesize(&format!("={}", a1.lexeme), vec![a0, a2])
+    }
+    fn visit_setindex_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr, a3: &Expr) -> String {
+        self.parenthesize("set-index", vec![a0, a2, a3])
+    }
+    fn visit_super_expr(&mut self, _a0: &Token, a1: &Token, _a2: &Cell<Option<usize>>) -> String {
+        format!("super.{}", a1.lexeme)
+    }
+    fn visit_this_expr(&mut self, _a0: &Token, _a1: &Cell<Option<usize>>) -> String {
+        String::from("this")
+    }
     fn visit_unary_expr(&mut self, a0: &Token, a1: &Expr) -> String {
-        self.parenthesize(&a0.lexeme, vec![a1])
+        self.parenthesize(&a0.lexeme.to_string(), vec![a1])
     }
-    fn visit_variable_expr(&mut self, a0: &Token) -> String {
+    fn visit_variable_expr(&mut self, a0: &Token, _a1: &Cell<Option<usize>>) -> String {
         format!("{}", a0.lexeme)
     }
-    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr) -> String {
-        todo!()
+    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr, _a2: &Cell<Option<usize>>) -> String {
+        self.parenthesize(&format!("={}", a0.lexeme), vec![a1])
     }
 }
 
@@ -0,0 +1,84 @@
+use super::{expr::Expr, stmt::Stmt};
+use crate::token::Span;
+
+impl Expr {
+    /// The stretch of source code this expression was parsed from. Grouping
+    /// parentheses aren't kept in the tree, so they aren't included.
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Assign(name, value, _) => name.span.to(value.span()),
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                left.span().to(right.span())
+            }
+            Expr::Call(callee, paren, _) => callee.span().to(paren.span),
+            Expr::Get(object, name) => object.span().to(name.span),
+            Expr::Grouping(expr) => expr.span(),
+            Expr::Index(object, bracket, _) => object.span().to(bracket.span),
+            Expr::Interpolation(start, parts) => {
+                start.span.to(parts.last().map_or(start.span, Expr::span))
+            }
+            Expr::List(opening, _, closing) | Expr::Map(opening, _, _, closing) => {
+                opening.span.to(closing.span)
+            }
+            Expr::Literal(token, _) => token.span,
+            Expr::Set(object, _, value) | Expr::SetIndex(object, _, _, value) => {
+                object.span().to(value.span())
+            }
+            Expr::Super(keyword, method, _) => keyword.span.to(method.span),
+            Expr::This(keyword, _) => keyword.span,
+            Expr::Unary(operator, right) => operator.span.to(right.span()),
+            Expr::Variable(name, _) => name.span,
+        }
+    }
+}
+
+impl Stmt {
+    /// The stretch of source code this statement was parsed from, from its first
+    /// token up to the last one it kept hold of. Semicolons and closing braces
+    /// aren't kept, so they're left out, and an empty block doesn't have a span
+    /// at all.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Stmt::Block(statements) => statements.iter().filter_map(Stmt::span).reduce(Span::to),
+            Stmt::Class(keyword, name, superclass, methods) => Some(
+                superclass
+                    .iter()
+                    .map(Expr::span)
+                    .chain(methods.iter().filter_map(Stmt::span))
+                    .fold(keyword.span.to(name.span), Span::to),
+            ),
+            Stmt::Expression(expr) => Some(expr.span()),
+            Stmt::Function(keyword, name, params, body) => Some(
+                params
+                    .iter()
+                    .map(|param| param.span)
+                    .chain(body.iter().filter_map(Stmt::span))
+                    .fold(
+                        keyword.as_ref().unwrap_or(name).span.to(name.span),
+                        Span::to,
+                    ),
+            ),
+            Stmt::If(keyword, condition, then_branch, else_branch) => Some(
+                then_branch
+                    .span()
+                    .into_iter()
+                    .chain(else_branch.as_ref().and_then(|stmt| stmt.span()))
+                    .fold(keyword.span.to(condition.span()), Span::to),
+            ),
+            Stmt::Print(keyword, expr) => Some(keyword.span.to(expr.span())),
+            Stmt::Return(keyword, value) => Some(match value {
+                Some(value) => keyword.span.to(value.span()),
+                None => keyword.span,
+            }),
+            Stmt::Var(keyword, name, initializer) => Some(match initializer {
+                Some(initializer) => keyword.span.to(initializer.span()),
+                None => keyword.span.to(name.span),
+            }),
+            Stmt::While(keyword, condition, body) => Some(
+                body.span()
+                    .into_iter()
+                    .fold(keyword.span.to(condition.span()), Span::to),
+            ),
+        }
+    }
+}
@@ -1,22 +1,47 @@
 use crate::ast::expr::Expr;
 use crate::token::Token;
+use std::rc::Rc;
 #[derive(Debug)]
 pub enum Stmt {
+    Block(Vec<Stmt>),
+    Class(Token, Token, Option<Expr>, Vec<Stmt>),
     Expression(Expr),
-    Print(Expr),
-    Var(Token, Option<Expr>),
+    Function(Option<Token>, Token, Vec<Token>, Rc<Vec<Stmt>>),
+    If(Token, Expr, Box<Stmt>, Option<Box<Stmt>>),
+    Print(Token, Expr),
+    Return(Token, Option<Expr>),
+    Var(Token, Token, Option<Expr>),
+    While(Token, Expr, Box<Stmt>),
 }
 impl Stmt {
     pub fn accept<R, V: Visitor<R>>(&self, visitor: &mut V) -> R {
         match self {
+            Stmt::Block(a0) => visitor.visit_block_stmt(a0),
+            Stmt::Class(a0, a1, a2, a3) => visitor.visit_class_stmt(a0, a1, a2, a3),
             Stmt::Expression(a0) => visitor.visit_expression_stmt(a0),
-            Stmt::Print(a0) => visitor.visit_print_stmt(a0),
-            Stmt::Var(a0, a1) => visitor.visit_var_stmt(a0, a1),
+            Stmt::Function(a0, a1, a2, a3) => visitor.visit_function_stmt(a0, a1, a2, a3),
+            Stmt::If(a0, a1, a2, a3) => visitor.visit_if_stmt(a0, a1, a2, a3),
+            Stmt::Print(a0, a1) => visitor.visit_print_stmt(a0, a1),
+            Stmt::Return(a0, a1) => visitor.visit_return_stmt(a0, a1),
+            Stmt::Var(a0, a1, a2) => visitor.visit_var_stmt(a0, a1, a2),
+            Stmt::While(a0, a1, a2) => visitor.visit_while_stmt(a0, a1, a2),
         }
     }
 }
 pub trait Visitor<R> {
+    fn visit_block_stmt(&mut self, a0: &[Stmt]) -> R;
+    fn visit_class_stmt(&mut self, a0: &Token, a1: &Token, a2: &Option<Expr>, a3: &[Stmt]) -> R;
     fn visit_expression_stmt(&mut self, a0: &Expr) -> R;
-    fn visit_print_stmt(&mut self, a0: &Expr) -> R;
-    fn visit_var_stmt(&mut self, a0: &Token, a1: &Option<Expr>) -> R;
+    fn visit_function_stmt(
+        &mut self,
+        a0: &Option<Token>,
+        a1: &Token,
+        a2: &[Token],
+        a3: &Rc<Vec<Stmt>>,
+    ) -> R;
+    fn visit_if_stmt(&mut self, a0: &Token, a1: &Expr, a2: &Stmt, a3: &Option<Box<Stmt>>) -> R;
+    fn visit_print_stmt(&mut self, a0: &Token, a1: &Expr) -> R;
+    fn visit_return_stmt(&mut self, a0: &Token, a1: &Option<Expr>) -> R;
+    fn visit_var_stmt(&mut self, a0: &Token, a1: &Token, a2: &Option<Expr>) -> R;
+    fn visit_while_stmt(&mut self, a0: &Token, a1: &Expr, a2: &Stmt) -> R;
 }
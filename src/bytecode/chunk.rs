@@ -0,0 +1,275 @@
+use crate::{
+    literal::Value,
+    symbol::Symbol,
+    token::{Lexeme, Span, Token},
+    token_type::TokenType,
+};
+use std::rc::Rc;
+
+/// One VM instruction. Operands, if any, follow the opcode in the byte stream.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u8)]
+pub enum OpCode {
+    /// u16 constant index. Pushes the constant.
+    Constant,
+    Nil,
+    True,
+    False,
+    Pop,
+    /// u8 stack slot, relative to the current call frame.
+    GetLocal,
+    /// u8 stack slot, relative to the current call frame.
+    SetLocal,
+    /// u16 index of the variable's name.
+    GetGlobal,
+    /// u16 index of the variable's name.
+    DefineGlobal,
+    /// u16 index of the variable's name.
+    SetGlobal,
+    /// u8 index into the current closure's upvalues.
+    GetUpvalue,
+    /// u8 index into the current closure's upvalues.
+    SetUpvalue,
+    /// u16 index of the property's name.
+    GetProperty,
+    /// u16 index of the property's name.
+    SetProperty,
+    /// u16 index of the method's name.
+    GetSuper,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Not,
+    Negate,
+    Print,
+    /// u16 distance to jump forwards.
+    Jump,
+    /// u16 distance to jump forwards. Leaves the condition on the stack.
+    JumpIfFalse,
+    /// u16 distance to jump backwards.
+    Loop,
+    /// u8 argument count.
+    Call,
+    /// u16 constant index of the function, then a pair of bytes for each of
+    /// its upvalues: 1 if it captures a local of the enclosing function (0 if
+    /// it's one of the enclosing function's upvalues), and then the index.
+    Closure,
+    CloseUpvalue,
+    Return,
+    /// u16 index of the class's name.
+    Class,
+    Inherit,
+    /// u16 index of the method's name.
+    Method,
+    /// u16 count of values to pop, which get turned into strings and joined.
+    Interpolate,
+    /// u16 count of values to pop, which become the elements of a new list.
+    BuildList,
+    /// Pops an index and a list or map, and pushes that element.
+    GetIndex,
+    /// Pops a value, an index and a list or map, sets that element, and
+    /// pushes the value.
+    SetIndex,
+    /// u16 count of entries. Pops a key and a value for each, which become a new map.
+    BuildMap,
+}
+
+impl OpCode {
+    const ALL: [OpCode; 42] = [
+        OpCode::Constant,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Pop,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetUpvalue,
+        OpCode::SetUpvalue,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Equal,
+        OpCode::Greater,
+        OpCode::GreaterEqual,
+        OpCode::Less,
+        OpCode::LessEqual,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Not,
+        OpCode::Negate,
+        OpCode::Print,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+        OpCode::Loop,
+        OpCode::Call,
+        OpCode::Closure,
+        OpCode::CloseUpvalue,
+        OpCode::Return,
+        OpCode::Class,
+        OpCode::Inherit,
+        OpCode::Method,
+        OpCode::Interpolate,
+        OpCode::BuildList,
+        OpCode::GetIndex,
+        OpCode::SetIndex,
+        OpCode::BuildMap,
+    ];
+
+    /// The opcode a byte stands for, if it stands for one at all.
+    pub fn from_byte(byte: u8) -> Option<OpCode> {
+        OpCode::ALL.get(byte as usize).copied()
+    }
+
+    /// How many bytes of operands follow the opcode. `Closure` is followed by
+    /// two more for each upvalue of its function, on top of this.
+    pub fn operand_len(self) -> usize {
+        match self {
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::GetUpvalue
+            | OpCode::SetUpvalue
+            | OpCode::Call => 1,
+            OpCode::Constant
+            | OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Jump
+            | OpCode::JumpIfFalse
+            | OpCode::Loop
+            | OpCode::Closure
+            | OpCode::Class
+            | OpCode::Method
+            | OpCode::Interpolate
+            | OpCode::BuildList
+            | OpCode::BuildMap => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// Where in the source code a byte of bytecode came from, so runtime errors
+/// can say where they happened.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Position {
+    /// The kind and text of the token it came from.
+    pub kind: TokenType,
+    pub lexeme: Lexeme,
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+impl Position {
+    pub fn of(token: &Token) -> Self {
+        Self {
+            kind: token.kind,
+            lexeme: token.lexeme.clone(),
+            line: token.line,
+            column: token.column,
+            span: token.span,
+        }
+    }
+
+    /// The token at this position, for building errors.
+    pub fn token(&self) -> Token {
+        Token::new(
+            self.kind,
+            self.lexeme.clone(),
+            Value::Nil,
+            self.line,
+            self.column,
+            self.span,
+        )
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            kind: TokenType::EOF,
+            lexeme: Lexeme::Text(Rc::from("")),
+            line: 0,
+            column: 0,
+            span: Span::default(),
+        }
+    }
+}
+
+/// A compiled function body: bytecode, plus the constants and names it refers to.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    /// One for every byte in `code`.
+    pub positions: Vec<Position>,
+    /// Where the operands of some instructions came from, so errors about
+    /// them can point at the one to blame. Each entry is keyed by the offset
+    /// of its instruction's last byte, which is where the VM is when it
+    /// reports an error, and they're in order.
+    pub operands: Vec<(usize, Vec<Span>)>,
+    pub constants: Vec<Value>,
+    /// The names of the globals, properties, methods and classes the code
+    /// uses, interned once up front rather than every time they're run.
+    pub names: Vec<Symbol>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, byte: u8, position: Position) {
+        self.code.push(byte);
+        self.positions.push(position);
+    }
+
+    /// Note where the operands of the instruction that was just written came from.
+    pub fn add_operands(&mut self, spans: Vec<Span>) {
+        self.operands.push((self.code.len() - 1, spans));
+    }
+
+    /// Where the operands of the instruction ending at `offset` came from, if
+    /// anyone noted it down.
+    pub fn operands_at(&self, offset: usize) -> &[Span] {
+        match self.operands.binary_search_by_key(&offset, |&(end, _)| end) {
+            Ok(index) => &self.operands[index].1,
+            Err(_) => &[],
+        }
+    }
+
+    /// Add a constant to the pool, handing back its index.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// Add a name to the table, handing back its index. Each name is only
+    /// stored once.
+    pub fn add_name(&mut self, name: Symbol) -> usize {
+        match self.names.iter().position(|&n| n == name) {
+            Some(index) => index,
+            None => {
+                self.names.push(name);
+                self.names.len() - 1
+            }
+        }
+    }
+
+    /// Read the big-endian u16 operand starting at `offset`.
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code[offset], self.code[offset + 1]])
+    }
+}
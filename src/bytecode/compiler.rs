@@ -0,0 +1,663 @@
+use super::{
+    chunk::{OpCode, Position},
+    object::{Function, Object},
+};
+use crate::{
+    ast::{
+        expr::{self, Expr},
+        stmt::{self, Stmt},
+    },
+    error::LoxError,
+    literal::Value,
+    symbol::Symbol,
+    token::{Lexeme, Span, Token},
+    token_type::TokenType,
+};
+use std::{cell::Cell, rc::Rc};
+
+/// Locals and upvalues are addressed with a single byte.
+const MAX_SLOTS: usize = u8::MAX as usize + 1;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug)]
+struct Local {
+    name: Symbol,
+    depth: usize,
+    /// Whether a closure holds onto this variable, so it has to be moved off
+    /// the stack when it goes out of scope.
+    is_captured: bool,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    /// Whether it's a local of the enclosing function, rather than one of
+    /// its upvalues.
+    is_local: bool,
+}
+
+/// Everything needed to compile one function. They nest as the compiler
+/// goes into function declarations.
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    /// The locals in scope, in stack slot order.
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> Self {
+        // slot 0 holds the function being called, or `this` in methods
+        let reserved = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => Symbol::THIS,
+            FunctionKind::Script | FunctionKind::Function => Symbol::intern(""),
+        };
+        Self {
+            function: Function::new(name),
+            kind,
+            locals: vec![Local {
+                name: reserved,
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: vec![],
+            scope_depth: 0,
+        }
+    }
+}
+
+/// Turns a resolved AST into bytecode for the VM. It assumes the resolver has
+/// already had its say, so the only errors left to catch are the limits of
+/// the bytecode format.
+pub struct Compiler {
+    /// The innermost function being compiled is last.
+    states: Vec<FunctionState>,
+    /// Where the code being compiled right now came from.
+    position: Position,
+    errors: Vec<LoxError>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Self {
+            states: vec![],
+            position: Position::default(),
+            errors: vec![],
+        }
+    }
+
+    /// Compile a whole program into a function that runs it. Like
+    /// `Interpreter::interpret`, running it hands back the value of the last
+    /// statement if that was an expression statement, or `nil` otherwise.
+    pub fn compile(&mut self, statements: &[Stmt]) -> Result<Rc<Function>, Vec<LoxError>> {
+        self.states
+            .push(FunctionState::new("", FunctionKind::Script));
+
+        match statements.split_last() {
+            Some((Stmt::Expression(last), rest)) => {
+                self.statements(rest);
+                self.expression(last);
+                self.emit_op(OpCode::Return);
+            }
+            _ => {
+                self.statements(statements);
+                self.emit_return();
+            }
+        }
+
+        let state = self.states.pop().unwrap();
+        if self.errors.is_empty() {
+            Ok(Rc::new(state.function))
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    /// Everything emitted from now on is blamed on `token`.
+    fn at(&mut self, token: &Token) {
+        self.position = Position::of(token);
+    }
+
+    fn error(&mut self, message: &str) {
+        let error = LoxError::Compile {
+            token: self.position.token(),
+            message: message.to_string(),
+            notes: Box::default(),
+        };
+        self.errors.push(error);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        let position = self.position.clone();
+        self.state().function.chunk.write(byte, position);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op as u8);
+    }
+
+    /// Note where the operands of the instruction just emitted came from, so
+    /// errors about them can point there, like they do in the tree-walker.
+    fn emit_operands(&mut self, spans: Vec<Span>) {
+        self.state().function.chunk.add_operands(spans);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        for byte in value.to_be_bytes().iter() {
+            self.emit_byte(*byte);
+        }
+    }
+
+    fn emit_op_u16(&mut self, op: OpCode, operand: u16) {
+        self.emit_op(op);
+        self.emit_u16(operand);
+    }
+
+    /// Return from the current function. Initializers always hand back `this`.
+    fn emit_return(&mut self) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn make_constant(&mut self, value: Value) -> u16 {
+        let index = self.state().function.chunk.add_constant(value);
+        if index > u16::MAX as usize {
+            self.error("Too many constants in one chunk.");
+            return 0;
+        }
+        index as u16
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let index = self.make_constant(value);
+        self.emit_op_u16(OpCode::Constant, index);
+    }
+
+    fn make_name(&mut self, name: &Token) -> u16 {
+        let index = self.state().function.chunk.add_name(name.name());
+        if index > u16::MAX as usize {
+            self.error("Too many names in one chunk.");
+            return 0;
+        }
+        index as u16
+    }
+
+    /// Emit a jump with a placeholder distance, handing back where to patch it.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op_u16(op, u16::MAX);
+        self.state().function.chunk.code.len() - 2
+    }
+
+    /// Point the jump at `offset` to the next instruction.
+    fn patch_jump(&mut self, offset: usize) {
+        let code = &self.state().function.chunk.code;
+        let distance = code.len() - offset - 2;
+        if distance > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+            return;
+        }
+        let bytes = (distance as u16).to_be_bytes();
+        let code = &mut self.state().function.chunk.code;
+        code[offset] = bytes[0];
+        code[offset + 1] = bytes[1];
+    }
+
+    fn emit_loop(&mut self, start: usize) {
+        self.emit_op(OpCode::Loop);
+        let distance = self.state().function.chunk.code.len() - start + 2;
+        if distance > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+        self.emit_u16(distance as u16);
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scope_depth += 1;
+    }
+
+    /// Throw away the scope's locals, moving any that were captured off the stack.
+    fn end_scope(&mut self) {
+        let state = self.state();
+        state.scope_depth -= 1;
+        let depth = state.scope_depth;
+
+        while let Some(local) = self.state().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.emit_op(op);
+            self.state().locals.pop();
+        }
+    }
+
+    /// Make a new local for whatever is on top of the stack.
+    fn add_local(&mut self, name: Symbol) {
+        if self.state().locals.len() == MAX_SLOTS {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        let depth = self.state().scope_depth;
+        self.state().locals.push(Local {
+            name,
+            depth,
+            is_captured: false,
+        });
+    }
+
+    fn is_local_scope(&mut self) -> bool {
+        self.state().scope_depth > 0
+    }
+
+    /// Bind whatever is on top of the stack to a new variable.
+    fn define_variable(&mut self, name: &Token) {
+        if self.is_local_scope() {
+            self.add_local(name.name());
+        } else {
+            let index = self.make_name(name);
+            self.emit_op_u16(OpCode::DefineGlobal, index);
+        }
+    }
+
+    fn resolve_local(&self, state: usize, name: Symbol) -> Option<u8> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    /// Find `name` in one of the functions enclosing `state`, threading it
+    /// through the upvalues of every function in between.
+    fn resolve_upvalue(&mut self, state: usize, name: Symbol) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+        if let Some(slot) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(state, slot, true));
+        }
+        let index = self.resolve_upvalue(state - 1, name)?;
+        Some(self.add_upvalue(state, index, false))
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool) -> u8 {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &mut self.states[state].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() == MAX_SLOTS {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+        upvalues.push(upvalue);
+        (upvalues.len() - 1) as u8
+    }
+
+    fn get_variable(&mut self, name: &Token) {
+        self.at(name);
+        let state = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(state, name.name()) {
+            self.emit_op(OpCode::GetLocal);
+            self.emit_byte(slot);
+        } else if let Some(index) = self.resolve_upvalue(state, name.name()) {
+            self.emit_op(OpCode::GetUpvalue);
+            self.emit_byte(index);
+        } else {
+            let index = self.make_name(name);
+            self.emit_op_u16(OpCode::GetGlobal, index);
+        }
+    }
+
+    fn set_variable(&mut self, name: &Token) {
+        self.at(name);
+        let state = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(state, name.name()) {
+            self.emit_op(OpCode::SetLocal);
+            self.emit_byte(slot);
+        } else if let Some(index) = self.resolve_upvalue(state, name.name()) {
+            self.emit_op(OpCode::SetUpvalue);
+            self.emit_byte(index);
+        } else {
+            let index = self.make_name(name);
+            self.emit_op_u16(OpCode::SetGlobal, index);
+        }
+    }
+
+    /// Compile a function body and leave a closure over it on the stack.
+    fn function(&mut self, name: &Token, params: &[Token], body: &[Stmt], kind: FunctionKind) {
+        self.states
+            .push(FunctionState::new(&name.name().as_str(), kind));
+        self.begin_scope();
+        for param in params {
+            self.add_local(param.name());
+        }
+        self.statements(body);
+        self.emit_return();
+
+        let state = self.states.pop().unwrap();
+        let mut function = state.function;
+        function.arity = params.len();
+        function.upvalue_count = state.upvalues.len();
+
+        self.at(name);
+        let index = self.make_constant(Value::Object(Object::Function(Rc::new(function))));
+        self.emit_op_u16(OpCode::Closure, index);
+        for upvalue in state.upvalues {
+            self.emit_byte(upvalue.is_local as u8);
+            self.emit_byte(upvalue.index);
+        }
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl expr::Visitor<()> for Compiler {
+    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr, _a2: &Cell<Option<usize>>) {
+        self.expression(a1);
+        self.set_variable(a0);
+    }
+    fn visit_binary_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) {
+        self.expression(a0);
+        self.expression(a2);
+
+        self.at(a1);
+        match a1.kind {
+            TokenType::GREATER => self.emit_op(OpCode::Greater),
+            TokenType::GREATER_EQUAL => self.emit_op(OpCode::GreaterEqual),
+            TokenType::LESS => self.emit_op(OpCode::Less),
+            TokenType::LESS_EQUAL => self.emit_op(OpCode::LessEqual),
+            TokenType::BANG_EQUAL => {
+                self.emit_op(OpCode::Equal);
+                self.emit_op(OpCode::Not);
+            }
+            TokenType::EQUAL_EQUAL => self.emit_op(OpCode::Equal),
+            TokenType::MINUS => self.emit_op(OpCode::Subtract),
+            TokenType::PLUS => self.emit_op(OpCode::Add),
+            TokenType::SLASH => self.emit_op(OpCode::Divide),
+            TokenType::STAR => self.emit_op(OpCode::Multiply),
+            _ => unreachable!(),
+        }
+        // only equality works on anything, so it can't go wrong
+        if !matches!(a1.kind, TokenType::BANG_EQUAL | TokenType::EQUAL_EQUAL) {
+            self.emit_operands(vec![a0.span(), a2.span()]);
+        }
+    }
+    fn visit_call_expr(&mut self, a0: &Expr, a1: &Token, a2: &[Expr]) {
+        self.expression(a0);
+        for argument in a2 {
+            self.expression(argument);
+        }
+        // the parser already stops calls from having more than 255 arguments
+        self.at(a1);
+        self.emit_op(OpCode::Call);
+        self.emit_byte(a2.len() as u8);
+    }
+    fn visit_get_expr(&mut self, a0: &Expr, a1: &Token) {
+        self.expression(a0);
+        self.at(a1);
+        let index = self.make_name(a1);
+        self.emit_op_u16(OpCode::GetProperty, index);
+    }
+    fn visit_grouping_expr(&mut self, a0: &Expr) {
+        self.expression(a0);
+    }
+    fn visit_index_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) {
+        self.expression(a0);
+        self.expression(a2);
+        self.at(a1);
+        self.emit_op(OpCode::GetIndex);
+        self.emit_operands(vec![a0.span(), a2.span()]);
+    }
+    fn visit_interpolation_expr(&mut self, a0: &Token, a1: &[Expr]) {
+        for part in a1 {
+            self.expression(part);
+        }
+        self.at(a0);
+        self.emit_op_u16(OpCode::Interpolate, a1.len() as u16);
+    }
+    fn visit_list_expr(&mut self, a0: &Token, a1: &[Expr], _a2: &Token) {
+        for element in a1 {
+            self.expression(element);
+        }
+        self.at(a0);
+        self.emit_op_u16(OpCode::BuildList, a1.len() as u16);
+    }
+    fn visit_literal_expr(&mut self, a0: &Token, a1: &Value) {
+        self.at(a0);
+        match a1 {
+            Value::Nil => self.emit_op(OpCode::Nil),
+            Value::Bool(true) => self.emit_op(OpCode::True),
+            Value::Bool(false) => self.emit_op(OpCode::False),
+            value => self.emit_constant(value.clone()),
+        }
+    }
+    fn visit_map_expr(&mut self, a0: &Token, a1: &[Expr], a2: &[Expr], _a3: &Token) {
+        for (key, value) in a1.iter().zip(a2) {
+            self.expression(key);
+            self.expression(value);
+        }
+        self.at(a0);
+        self.emit_op_u16(OpCode::BuildMap, a1.len() as u16);
+        self.emit_operands(a1.iter().map(Expr::span).collect());
+    }
+    fn visit_logical_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) {
+        self.expression(a0);
+        self.at(a1);
+
+        // short-circuit, leaving the operand itself on the stack rather than a bool
+        let end_jump = match a1.kind {
+            TokenType::AND => self.emit_jump(OpCode::JumpIfFalse),
+            TokenType::OR => {
+                let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                let end_jump = self.emit_jump(OpCode::Jump);
+                self.patch_jump(else_jump);
+                end_jump
+            }
+            _ => unreachable!(),
+        };
+
+        self.emit_op(OpCode::Pop);
+        self.expression(a2);
+        self.patch_jump(end_jump);
+    }
+    fn visit_set_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) {
+        self.expression(a0);
+        self.expression(a2);
+        self.at(a1);
+        let index = self.make_name(a1);
+        self.emit_op_u16(OpCode::SetProperty, index);
+    }
+    fn visit_setindex_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr, a3: &Expr) {
+        self.expression(a0);
+        self.expression(a2);
+        self.expression(a3);
+        self.at(a1);
+        self.emit_op(OpCode::SetIndex);
+        self.emit_operands(vec![a0.span(), a2.span()]);
+    }
+    fn visit_super_expr(&mut self, a0: &Token, a1: &Token, _a2: &Cell<Option<usize>>) {
+        let mut this = a0.clone();
+        this.lexeme = Lexeme::Name(Symbol::THIS);
+        self.get_variable(&this);
+        self.get_variable(a0);
+
+        self.at(a1);
+        let index = self.make_name(a1);
+        self.emit_op_u16(OpCode::GetSuper, index);
+    }
+    fn visit_this_expr(&mut self, a0: &Token, _a1: &Cell<Option<usize>>) {
+        self.get_variable(a0);
+    }
+    fn visit_unary_expr(&mut self, a0: &Token, a1: &Expr) {
+        self.expression(a1);
+        self.at(a0);
+        match a0.kind {
+            TokenType::MINUS => {
+                self.emit_op(OpCode::Negate);
+                self.emit_operands(vec![a1.span()]);
+            }
+            TokenType::BANG => self.emit_op(OpCode::Not),
+            _ => unreachable!(),
+        }
+    }
+    fn visit_variable_expr(&mut self, a0: &Token, _a1: &Cell<Option<usize>>) {
+        self.get_variable(a0);
+    }
+}
+
+impl stmt::Visitor<()> for Compiler {
+    fn visit_block_stmt(&mut self, a0: &[Stmt]) {
+        self.begin_scope();
+        self.statements(a0);
+        self.end_scope();
+    }
+    fn visit_class_stmt(&mut self, _a0: &Token, a1: &Token, a2: &Option<Expr>, a3: &[Stmt]) {
+        self.at(a1);
+        let index = self.make_name(a1);
+        self.emit_op_u16(OpCode::Class, index);
+        self.define_variable(a1);
+
+        if let Some(superclass) = a2 {
+            self.expression(superclass);
+
+            // methods close over an extra scope that holds `super`
+            self.begin_scope();
+            self.add_local(Symbol::SUPER);
+
+            self.get_variable(a1);
+            if let Expr::Variable(name, _) = superclass {
+                self.at(name);
+            }
+            self.emit_op(OpCode::Inherit);
+        }
+
+        // keep the class on the stack while its methods are attached
+        self.get_variable(a1);
+        for method in a3 {
+            if let Stmt::Function(_, name, params, body) = method {
+                let kind = if name.name() == Symbol::INIT {
+                    FunctionKind::Initializer
+                } else {
+                    FunctionKind::Method
+                };
+                self.function(name, params, body, kind);
+                let index = self.make_name(name);
+                self.emit_op_u16(OpCode::Method, index);
+            }
+        }
+        self.emit_op(OpCode::Pop);
+
+        if a2.is_some() {
+            self.end_scope();
+        }
+    }
+    fn visit_expression_stmt(&mut self, a0: &Expr) {
+        self.expression(a0);
+        self.emit_op(OpCode::Pop);
+    }
+    fn visit_function_stmt(
+        &mut self,
+        _a0: &Option<Token>,
+        a1: &Token,
+        a2: &[Token],
+        a3: &Rc<Vec<Stmt>>,
+    ) {
+        // a local function is in scope inside its own body, so it can call itself
+        if self.is_local_scope() {
+            self.add_local(a1.name());
+            self.function(a1, a2, a3, FunctionKind::Function);
+        } else {
+            self.function(a1, a2, a3, FunctionKind::Function);
+            self.define_variable(a1);
+        }
+    }
+    fn visit_if_stmt(&mut self, _a0: &Token, a1: &Expr, a2: &Stmt, a3: &Option<Box<Stmt>>) {
+        self.expression(a1);
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        a2.accept(self);
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+        self.patch_jump(then_jump);
+        self.emit_op(OpCode::Pop);
+        if let Some(else_branch) = a3 {
+            else_branch.accept(self);
+        }
+        self.patch_jump(else_jump);
+    }
+    fn visit_print_stmt(&mut self, _a0: &Token, a1: &Expr) {
+        self.expression(a1);
+        self.emit_op(OpCode::Print);
+    }
+    fn visit_return_stmt(&mut self, a0: &Token, a1: &Option<Expr>) {
+        self.at(a0);
+        match a1 {
+            Some(value) => {
+                self.expression(value);
+                self.emit_op(OpCode::Return);
+            }
+            None => self.emit_return(),
+        }
+    }
+    fn visit_var_stmt(&mut self, _a0: &Token, a1: &Token, a2: &Option<Expr>) {
+        match a2 {
+            Some(initializer) => self.expression(initializer),
+            None => {
+                self.at(a1);
+                self.emit_op(OpCode::Nil);
+            }
+        }
+        self.at(a1);
+        self.define_variable(a1);
+    }
+    fn visit_while_stmt(&mut self, _a0: &Token, a1: &Expr, a2: &Stmt) {
+        let loop_start = self.state().function.chunk.code.len();
+        self.expression(a1);
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_op(OpCode::Pop);
+        a2.accept(self);
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_op(OpCode::Pop);
+    }
+}
@@ -0,0 +1,138 @@
+use super::{
+    chunk::{Chunk, OpCode},
+    object::{Function, Object},
+};
+use crate::literal::Value;
+use std::fmt::Write;
+
+/// Turn a compiled function into a listing for humans, followed by listings
+/// of every function declared inside it. Looks like this:
+///
+/// ```text
+/// == <script> ==
+/// 0000    1 Constant            0 '1'
+/// 0003    | Constant            1 '2'
+/// 0006    | Add
+/// 0007    | Print
+/// 0008    2 Closure             2 <fn f>
+/// 0011    | DefineGlobal        0 'f'
+/// ```
+///
+/// The second column is the line number, or `|` if it's the same as the
+/// instruction before.
+pub fn disassemble(function: &Function) -> String {
+    let mut out = String::new();
+    disassemble_function(&mut out, function);
+    out
+}
+
+fn disassemble_function(out: &mut String, function: &Function) {
+    writeln!(out, "== {} ==", function).unwrap();
+
+    let chunk = &function.chunk;
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        offset = instruction(out, chunk, offset);
+    }
+
+    for constant in &chunk.constants {
+        if let Value::Object(Object::Function(nested)) = constant {
+            writeln!(out).unwrap();
+            disassemble_function(out, nested);
+        }
+    }
+}
+
+/// Write out the instruction at `offset`, handing back the offset of the next one.
+fn instruction(out: &mut String, chunk: &Chunk, offset: usize) -> usize {
+    write!(out, "{:04} ", offset).unwrap();
+    let line = chunk.positions[offset].line;
+    if offset > 0 && line == chunk.positions[offset - 1].line {
+        write!(out, "   | ").unwrap();
+    } else {
+        write!(out, "{:4} ", line).unwrap();
+    }
+
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => {
+            writeln!(out, "<unknown opcode {}>", chunk.code[offset]).unwrap();
+            return offset + 1;
+        }
+    };
+    let name = format!("{:?}", op);
+
+    match op {
+        OpCode::Constant => {
+            let index = chunk.read_u16(offset + 1) as usize;
+            writeln!(out, "{:<16} {:4} '{}'", name, index, chunk.constants[index]).unwrap();
+            offset + 3
+        }
+        OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index = chunk.read_u16(offset + 1) as usize;
+            writeln!(out, "{:<16} {:4} '{}'", name, index, chunk.names[index]).unwrap();
+            offset + 3
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => {
+            writeln!(out, "{:<16} {:4}", name, chunk.code[offset + 1]).unwrap();
+            offset + 2
+        }
+        OpCode::Interpolate | OpCode::BuildList | OpCode::BuildMap => {
+            writeln!(out, "{:<16} {:4}", name, chunk.read_u16(offset + 1)).unwrap();
+            offset + 3
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let distance = chunk.read_u16(offset + 1) as usize;
+            let target = if op == OpCode::Loop {
+                offset + 3 - distance
+            } else {
+                offset + 3 + distance
+            };
+            writeln!(out, "{:<16} {:4} -> {:04}", name, offset, target).unwrap();
+            offset + 3
+        }
+        OpCode::Closure => {
+            let index = chunk.read_u16(offset + 1) as usize;
+            let constant = &chunk.constants[index];
+            writeln!(out, "{:<16} {:4} {}", name, index, constant).unwrap();
+
+            let upvalue_count = match constant {
+                Value::Object(Object::Function(function)) => function.upvalue_count,
+                _ => 0,
+            };
+            let mut offset = offset + 3;
+            for _ in 0..upvalue_count {
+                let kind = if chunk.code[offset] == 1 {
+                    "local"
+                } else {
+                    "upvalue"
+                };
+                writeln!(
+                    out,
+                    "{:04}    |                     {} {}",
+                    offset,
+                    kind,
+                    chunk.code[offset + 1]
+                )
+                .unwrap();
+                offset += 2;
+            }
+            offset
+        }
+        _ => {
+            writeln!(out, "{}", name).unwrap();
+            offset + 1
+        }
+    }
+}
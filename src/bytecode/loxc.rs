@@ -0,0 +1,518 @@
+//! The `.loxc` file format, for Lox programs that have already been compiled
+//! to bytecode.
+//!
+//! All integers in the file are little-endian, apart from the operands inside
+//! `code`, which are stored exactly as the VM runs them: big-endian, as
+//! [`Chunk::read_u16`] reads them. A file is a header followed by the
+//! top-level script, stored as a function:
+//!
+//! | Field   | Type     | Notes                                  |
+//! |---------|----------|----------------------------------------|
+//! | magic   | 4 bytes  | `LOXC`                                 |
+//! | version | u16      | [`VERSION`]; anything else is rejected |
+//! | script  | function | see below                              |
+//!
+//! A function is:
+//!
+//! | Field          | Type                  | Notes                                     |
+//! |----------------|-----------------------|-------------------------------------------|
+//! | name           | string                | empty for the top-level script            |
+//! | arity          | u8                    |                                           |
+//! | upvalue count  | u16                   |                                           |
+//! | constant count | u32                   |                                           |
+//! | constants      | constant × count      | the constant pool                         |
+//! | name count     | u32                   |                                           |
+//! | names          | string × count        | the globals, properties and so on it uses |
+//! | code length    | u32                   |                                           |
+//! | code           | bytes                 | see [`OpCode`] for what they mean         |
+//! | run count      | u32                   |                                           |
+//! | line table     | run × count           | must cover exactly `code length` bytes    |
+//! | operand count  | u32                   |                                           |
+//! | operand table  | operands × count      | in order of offset                        |
+//!
+//! A string is a u32 byte length followed by that many bytes of UTF-8. A
+//! constant is a one-byte tag followed by its value: `0` and an f64 for a
+//! number, `1` and a string for a string, or `2` and a function for a
+//! function. A run in the line table says where in the source code a stretch
+//! of bytes came from: how many bytes (u32), the kind of token (u8) and its
+//! lexeme (string), then the line, column, span start and span end (all u32).
+//! An entry in the operand table says where an instruction's operands came
+//! from: the offset of its last byte (u32), a span count (u32), then the
+//! start and end (u32) of each span.
+
+use super::{
+    chunk::{Chunk, OpCode, Position},
+    object::{Function, Object},
+};
+use crate::{
+    literal::Value,
+    symbol::Symbol,
+    token::{Lexeme, Span},
+    token_type::TokenType,
+};
+use std::{
+    convert::TryFrom,
+    io::{self, ErrorKind},
+    rc::Rc,
+};
+
+pub const MAGIC: &[u8; 4] = b"LOXC";
+pub const VERSION: u16 = 4;
+
+const TAG_NUMBER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+
+/// Turn a compiled script into the bytes of a `.loxc` file.
+pub fn serialize(script: &Function) -> io::Result<Vec<u8>> {
+    let mut out = MAGIC.to_vec();
+    out.extend_from_slice(&VERSION.to_le_bytes());
+    write_function(&mut out, script)?;
+    Ok(out)
+}
+
+/// Load a compiled script back out of the bytes of a `.loxc` file, checking
+/// that its structure holds together and that the VM can run its code without
+/// running off the end of anything. Values of the wrong type on the stack are
+/// left for the VM to report as runtime errors, like anything else a program
+/// does wrong.
+pub fn deserialize(bytes: &[u8]) -> io::Result<Rc<Function>> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err(invalid("not a compiled Lox program"));
+    }
+    let version = reader.u16()?;
+    if version != VERSION {
+        return Err(invalid(format!(
+            "unsupported .loxc version {} (expected {})",
+            version, VERSION
+        )));
+    }
+
+    let script = reader.function()?;
+    if script.arity != 0 || script.upvalue_count != 0 {
+        return Err(invalid(
+            "the script can't take arguments or capture variables",
+        ));
+    }
+    if reader.offset != bytes.len() {
+        return Err(invalid("trailing bytes after the script"));
+    }
+    Ok(Rc::new(script))
+}
+
+fn invalid<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.into())
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) -> io::Result<()> {
+    let value = u32::try_from(value).map_err(|_| invalid("too big for a .loxc file"))?;
+    out.extend_from_slice(&value.to_le_bytes());
+    Ok(())
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) -> io::Result<()> {
+    write_u32(out, s.len())?;
+    out.extend_from_slice(s.as_bytes());
+    Ok(())
+}
+
+fn write_function(out: &mut Vec<u8>, function: &Function) -> io::Result<()> {
+    let chunk = &function.chunk;
+    write_string(out, &function.name)?;
+    out.push(function.arity as u8);
+    out.extend_from_slice(&(function.upvalue_count as u16).to_le_bytes());
+
+    write_u32(out, chunk.constants.len())?;
+    for constant in &chunk.constants {
+        match constant {
+            Value::Number(n) => {
+                out.push(TAG_NUMBER);
+                out.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::String(s) => {
+                out.push(TAG_STRING);
+                write_string(out, s)?;
+            }
+            Value::Object(Object::Function(nested)) => {
+                out.push(TAG_FUNCTION);
+                write_function(out, nested)?;
+            }
+            other => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("can't store {} in a .loxc file", other.describe()),
+                ))
+            }
+        }
+    }
+
+    write_u32(out, chunk.names.len())?;
+    for name in &chunk.names {
+        name.with_str(|name| write_string(out, name))?;
+    }
+
+    write_u32(out, chunk.code.len())?;
+    out.extend_from_slice(&chunk.code);
+
+    // runs of bytes from the same place, since most instructions share one
+    let mut runs: Vec<(usize, Position)> = vec![];
+    for position in &chunk.positions {
+        match runs.last_mut() {
+            Some((count, last)) if last == position => *count += 1,
+            _ => runs.push((1, position.clone())),
+        }
+    }
+    write_u32(out, runs.len())?;
+    for (count, position) in runs {
+        write_u32(out, count)?;
+        out.push(position.kind as u8);
+        position
+            .lexeme
+            .with_str(|lexeme| write_string(out, lexeme))?;
+        write_u32(out, position.line)?;
+        write_u32(out, position.column)?;
+        write_u32(out, position.span.start)?;
+        write_u32(out, position.span.end)?;
+    }
+
+    write_u32(out, chunk.operands.len())?;
+    for (offset, spans) in &chunk.operands {
+        write_u32(out, *offset)?;
+        write_u32(out, spans.len())?;
+        for span in spans {
+            write_u32(out, span.start)?;
+            write_u32(out, span.end)?;
+        }
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len());
+        match end {
+            Some(end) => {
+                let bytes = &self.bytes[self.offset..end];
+                self.offset = end;
+                Ok(bytes)
+            }
+            None => Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "compiled Lox program is cut short",
+            )),
+        }
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<usize> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string isn't valid UTF-8"))
+    }
+
+    fn function(&mut self) -> io::Result<Function> {
+        let mut function = Function::new(self.string()?);
+        function.arity = self.u8()? as usize;
+        function.upvalue_count = self.u16()? as usize;
+
+        let mut chunk = Chunk::new();
+        let constant_count = self.u32()?;
+        for _ in 0..constant_count {
+            let constant = match self.u8()? {
+                TAG_NUMBER => Value::Number(self.f64()?),
+                TAG_STRING => Value::String(Rc::from(self.string()?)),
+                TAG_FUNCTION => Value::Object(Object::Function(Rc::new(self.function()?))),
+                tag => return Err(invalid(format!("unknown constant tag {}", tag))),
+            };
+            chunk.add_constant(constant);
+        }
+
+        let name_count = self.u32()?;
+        for _ in 0..name_count {
+            chunk.names.push(Symbol::intern(&self.string()?));
+        }
+
+        let code_len = self.u32()?;
+        chunk.code = self.take(code_len)?.to_vec();
+
+        let run_count = self.u32()?;
+        for _ in 0..run_count {
+            let count = self.u32()?;
+            let kind = self.u8()?;
+            let kind = TokenType::from_byte(kind)
+                .ok_or_else(|| invalid(format!("unknown token kind {}", kind)))?;
+            let position = Position {
+                kind,
+                lexeme: Lexeme::new(kind, &self.string()?),
+                line: self.u32()?,
+                column: self.u32()?,
+                span: Span::new(self.u32()?, self.u32()?),
+            };
+            if chunk.positions.len() + count > code_len {
+                return Err(invalid("line table is longer than the code"));
+            }
+            let len = chunk.positions.len();
+            chunk.positions.resize(len + count, position);
+        }
+        if chunk.positions.len() != code_len {
+            return Err(invalid("line table doesn't cover all the code"));
+        }
+
+        let operand_count = self.u32()?;
+        for _ in 0..operand_count {
+            let offset = self.u32()?;
+            let out_of_order = chunk
+                .operands
+                .last()
+                .is_some_and(|&(last, _)| last >= offset);
+            if offset >= code_len || out_of_order {
+                return Err(invalid(format!(
+                    "operands for nonexistent instruction {}",
+                    offset
+                )));
+            }
+            let span_count = self.u32()?;
+            let mut spans = vec![];
+            for _ in 0..span_count {
+                spans.push(Span::new(self.u32()?, self.u32()?));
+            }
+            chunk.operands.push((offset, spans));
+        }
+
+        function.chunk = chunk;
+        validate(&function)?;
+        Ok(function)
+    }
+}
+
+/// Make sure every instruction is real, has all its operands, and only
+/// refers to constants, names, jump targets, locals and upvalues that exist. Then
+/// follow every path through the code, to check the stack always has as
+/// many values on it as the next instruction takes, and that it's the same
+/// depth whichever way an instruction is reached.
+fn validate(function: &Function) -> io::Result<()> {
+    let chunk = &function.chunk;
+    let code = &chunk.code;
+
+    // where each instruction starts, along with what it is and where the next one starts
+    let mut instructions = vec![None; code.len()];
+    let mut offset = 0;
+    while offset < code.len() {
+        let op = OpCode::from_byte(code[offset])
+            .ok_or_else(|| invalid(format!("unknown opcode {} at {}", code[offset], offset)))?;
+        let mut next = offset + 1 + op.operand_len();
+        if next > code.len() {
+            return Err(invalid(format!(
+                "{:?} at {} is missing operands",
+                op, offset
+            )));
+        }
+
+        match op {
+            OpCode::Constant => {
+                constant(chunk, offset)?;
+            }
+            OpCode::GetGlobal
+            | OpCode::DefineGlobal
+            | OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Class
+            | OpCode::Method => {
+                let index = chunk.read_u16(offset + 1) as usize;
+                if index >= chunk.names.len() {
+                    return Err(invalid(format!(
+                        "name {} at {} doesn't exist",
+                        index, offset
+                    )));
+                }
+            }
+            OpCode::Closure => match constant(chunk, offset)? {
+                Value::Object(Object::Function(nested)) => {
+                    next += 2 * nested.upvalue_count;
+                    if next > code.len() {
+                        return Err(invalid(format!(
+                            "closure at {} is missing upvalues",
+                            offset
+                        )));
+                    }
+                }
+                _ => return Err(invalid(format!("closure at {} needs a function", offset))),
+            },
+            _ => {}
+        }
+        instructions[offset] = Some((op, next));
+        offset = next;
+    }
+
+    // how deep the stack is when each instruction runs. It starts out with
+    // the function itself in slot 0, and then its arguments
+    let mut depths = vec![None; code.len()];
+    let mut pending = vec![(0, 1 + function.arity)];
+    while let Some((offset, depth)) = pending.pop() {
+        let (op, next) = match instructions.get(offset) {
+            Some(&Some(instruction)) => instruction,
+            Some(None) => {
+                return Err(invalid(format!(
+                    "jump into the middle of nowhere: {}",
+                    offset
+                )))
+            }
+            None => return Err(invalid(format!("{} runs off the end", function))),
+        };
+        match depths[offset] {
+            Some(previous) if previous == depth => continue,
+            Some(_) => {
+                return Err(invalid(format!(
+                    "stack depth at {} depends on how it got there",
+                    offset
+                )))
+            }
+            None => depths[offset] = Some(depth),
+        }
+
+        let operand = || code[offset + 1] as usize;
+        let count = || function.chunk.read_u16(offset + 1) as usize;
+        let (pops, pushes) = match op {
+            OpCode::Constant
+            | OpCode::Nil
+            | OpCode::True
+            | OpCode::False
+            | OpCode::GetGlobal
+            | OpCode::Class => (0, 1),
+            OpCode::Pop
+            | OpCode::DefineGlobal
+            | OpCode::Print
+            | OpCode::CloseUpvalue
+            | OpCode::Return => (1, 0),
+            OpCode::GetLocal | OpCode::SetLocal => {
+                if operand() >= depth {
+                    return Err(invalid(format!(
+                        "{:?} at {} uses a slot that doesn't exist",
+                        op, offset
+                    )));
+                }
+                if op == OpCode::GetLocal {
+                    (0, 1)
+                } else {
+                    (1, 1)
+                }
+            }
+            OpCode::GetUpvalue | OpCode::SetUpvalue => {
+                if operand() >= function.upvalue_count {
+                    return Err(invalid(format!(
+                        "{:?} at {} uses an upvalue that doesn't exist",
+                        op, offset
+                    )));
+                }
+                if op == OpCode::GetUpvalue {
+                    (0, 1)
+                } else {
+                    (1, 1)
+                }
+            }
+            OpCode::Closure => {
+                for pair in code[offset + 3..next].chunks(2) {
+                    // a local function can capture itself, in the slot it's about to go in
+                    let exists = match pair[0] {
+                        1 => (pair[1] as usize) <= depth,
+                        0 => (pair[1] as usize) < function.upvalue_count,
+                        _ => false,
+                    };
+                    if !exists {
+                        return Err(invalid(format!(
+                            "closure at {} captures a variable that doesn't exist",
+                            offset
+                        )));
+                    }
+                }
+                (0, 1)
+            }
+            OpCode::Jump | OpCode::Loop => (0, 0),
+            OpCode::SetGlobal
+            | OpCode::GetProperty
+            | OpCode::Not
+            | OpCode::Negate
+            | OpCode::JumpIfFalse => (1, 1),
+            OpCode::SetProperty
+            | OpCode::GetSuper
+            | OpCode::Equal
+            | OpCode::Greater
+            | OpCode::GreaterEqual
+            | OpCode::Less
+            | OpCode::LessEqual
+            | OpCode::Add
+            | OpCode::Subtract
+            | OpCode::Multiply
+            | OpCode::Divide
+            | OpCode::Inherit
+            | OpCode::Method
+            | OpCode::GetIndex => (2, 1),
+            OpCode::SetIndex => (3, 1),
+            OpCode::Call => (operand() + 1, 1),
+            OpCode::Interpolate | OpCode::BuildList => (count(), 1),
+            OpCode::BuildMap => (count() * 2, 1),
+        };
+        // slot 0 belongs to the function, so nothing can take it off the stack
+        if pops >= depth {
+            return Err(invalid(format!(
+                "{:?} at {} takes more off the stack than there is",
+                op, offset
+            )));
+        }
+        let depth = depth - pops + pushes;
+
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((next + chunk.read_u16(offset + 1) as usize, depth)),
+            OpCode::JumpIfFalse => {
+                pending.push((next, depth));
+                pending.push((next + chunk.read_u16(offset + 1) as usize, depth));
+            }
+            OpCode::Loop => match next.checked_sub(chunk.read_u16(offset + 1) as usize) {
+                Some(target) => pending.push((target, depth)),
+                None => return Err(invalid(format!("loop at {} jumps too far", offset))),
+            },
+            _ => pending.push((next, depth)),
+        }
+    }
+    Ok(())
+}
+
+/// The constant an instruction's u16 operand refers to.
+fn constant(chunk: &Chunk, offset: usize) -> io::Result<&Value> {
+    let index = chunk.read_u16(offset + 1) as usize;
+    chunk
+        .constants
+        .get(index)
+        .ok_or_else(|| invalid(format!("constant {} at {} doesn't exist", index, offset)))
+}
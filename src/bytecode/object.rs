@@ -0,0 +1,147 @@
+use super::chunk::Chunk;
+use crate::{literal::Value, symbol::Symbol};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+/// A function compiled to bytecode. Only ever seen by Lox code wrapped up in a `Closure`.
+#[derive(Debug)]
+pub struct Function {
+    /// Empty for the top-level script.
+    pub name: String,
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+}
+
+impl Function {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::new(),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
+
+/// A variable captured by a closure. It lives on the VM's stack until the
+/// variable goes out of scope, then moves in here.
+#[derive(Debug)]
+pub enum Upvalue {
+    /// Still on the stack, at this slot.
+    Open(usize),
+    Closed(Value),
+}
+
+/// A function along with the variables it captured.
+#[derive(Debug)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+impl Closure {
+    pub fn new(function: Rc<Function>) -> Self {
+        Self {
+            upvalues: Vec::with_capacity(function.upvalue_count),
+            function,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    /// Inherited methods are copied in here too, so lookups never have to
+    /// walk up to the superclass.
+    pub methods: HashMap<Symbol, Rc<Closure>>,
+}
+
+impl Class {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            methods: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Instance {
+    pub class: Rc<RefCell<Class>>,
+    pub fields: HashMap<Symbol, Value>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<RefCell<Class>>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+}
+
+/// A method that's been pulled off an instance, remembering which one.
+#[derive(Debug)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Closure>,
+}
+
+/// The values only the bytecode VM makes.
+#[derive(Debug, Clone)]
+pub enum Object {
+    Function(Rc<Function>),
+    Closure(Rc<Closure>),
+    Class(Rc<RefCell<Class>>),
+    Instance(Rc<RefCell<Instance>>),
+    BoundMethod(Rc<BoundMethod>),
+}
+
+impl Object {
+    /// See `Value::describe`.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Object::Function(_) | Object::Closure(_) | Object::BoundMethod(_) => "a function",
+            Object::Class(_) => "a class",
+            Object::Instance(_) => "an instance",
+        }
+    }
+}
+
+/// Objects are only ever equal to themselves.
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Function(a), Object::Function(b)) => Rc::ptr_eq(a, b),
+            (Object::Closure(a), Object::Closure(b)) => Rc::ptr_eq(a, b),
+            (Object::Class(a), Object::Class(b)) => Rc::ptr_eq(a, b),
+            (Object::Instance(a), Object::Instance(b)) => Rc::ptr_eq(a, b),
+            (Object::BoundMethod(a), Object::BoundMethod(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::Function(function) => write!(f, "{}", function),
+            Object::Closure(closure) => write!(f, "{}", closure.function),
+            Object::Class(class) => write!(f, "{}", class.borrow().name),
+            Object::Instance(instance) => {
+                write!(f, "{} instance", instance.borrow().class.borrow().name)
+            }
+            Object::BoundMethod(bound) => write!(f, "{}", bound.method.function),
+        }
+    }
+}
@@ -0,0 +1,583 @@
+use super::{
+    chunk::{Chunk, OpCode},
+    object::{BoundMethod, Class, Closure, Function, Instance, Object, Upvalue},
+};
+use crate::{
+    diagnostic::suggestion,
+    error::{LoxError, RuntimeErrorKind},
+    interpreter::{index_culprit, is_equal, is_truthy, MAX_CALL_DEPTH},
+    literal::{Key, Value},
+    map::LoxMap,
+    native,
+    symbol::Symbol,
+    Output,
+};
+use std::{cell::RefCell, collections::HashMap, io, rc::Rc};
+
+/// How deep calls can nest before it's a stack overflow. There's a frame for
+/// the script itself too, so Lox calls nest exactly as deep as they can in
+/// the tree-walker.
+const FRAMES_MAX: usize = MAX_CALL_DEPTH + 1;
+
+/// A function that's in the middle of running.
+struct CallFrame {
+    closure: Rc<Closure>,
+    /// Offset of the next byte to run in the closure's chunk.
+    ip: usize,
+    /// Where the frame's stack slots start. Slot 0 is the function itself,
+    /// or `this` for methods.
+    slots: usize,
+}
+
+/// A stack-based virtual machine that runs bytecode from the `Compiler`.
+/// Globals defined by one program stick around for the next.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    globals: HashMap<Symbol, Value>,
+    /// Upvalues still pointing into the stack, so closures made in the same
+    /// scope share them.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    /// Where `print` goes.
+    output: Output,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut globals = HashMap::new();
+        for native in native::builtins() {
+            globals.insert(native.name, Value::Native(Rc::new(native)));
+        }
+
+        Self {
+            stack: vec![],
+            frames: vec![],
+            globals,
+            open_upvalues: vec![],
+            output: Rc::new(RefCell::new(io::stdout())),
+        }
+    }
+
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    pub fn globals(&self) -> &HashMap<Symbol, Value> {
+        &self.globals
+    }
+
+    pub fn globals_mut(&mut self) -> &mut HashMap<Symbol, Value> {
+        &mut self.globals
+    }
+
+    /// Run a compiled program. Hands back whatever it returns, or the first
+    /// runtime error.
+    pub fn interpret(&mut self, script: Rc<Function>) -> Result<Value, LoxError> {
+        let closure = Rc::new(Closure::new(script));
+        self.stack
+            .push(Value::Object(Object::Closure(Rc::clone(&closure))));
+        let result = self.call(closure, 0).and_then(|_| self.run());
+        if result.is_err() {
+            // leave things tidy for the next program
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<Value, LoxError> {
+        loop {
+            let byte = self.read_byte();
+            let op = OpCode::from_byte(byte).expect("the compiler only emits valid opcodes");
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.push(constant);
+                }
+                OpCode::Nil => self.push(Value::Nil),
+                OpCode::True => self.push(Value::Bool(true)),
+                OpCode::False => self.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_name();
+                    match self.globals.get(&name) {
+                        Some(value) => self.push(value.clone()),
+                        None => return Err(self.undefined_variable(name)),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_name();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_name();
+                    if !self.globals.contains_key(&name) {
+                        return Err(self.undefined_variable(name));
+                    }
+                    self.globals.insert(name, self.peek(0).clone());
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                    let value = self.peek(0).clone();
+                    let mut upvalue = upvalue.borrow_mut();
+                    match &mut *upvalue {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    }
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_name();
+                    let instance = match self.peek(0) {
+                        Value::Object(Object::Instance(instance)) => Rc::clone(instance),
+                        _ => return Err(self.type_mismatch("Only instances have properties.")),
+                    };
+
+                    // fields shadow methods
+                    let field = instance.borrow().fields.get(&name).cloned();
+                    match field {
+                        Some(value) => {
+                            self.pop();
+                            self.push(value);
+                        }
+                        None => {
+                            let class = Rc::clone(&instance.borrow().class);
+                            self.bind_method(&class, name)?;
+                        }
+                    }
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_name();
+                    let instance = match self.peek(1) {
+                        Value::Object(Object::Instance(instance)) => Rc::clone(instance),
+                        _ => return Err(self.type_mismatch("Only instances have fields.")),
+                    };
+
+                    let value = self.pop();
+                    instance.borrow_mut().fields.insert(name, value.clone());
+                    self.pop();
+                    self.push(value);
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_name();
+                    let superclass = match self.pop() {
+                        Value::Object(Object::Class(class)) => class,
+                        _ => return Err(self.type_mismatch("Superclass must be a class.")),
+                    };
+                    self.bind_method(&superclass, name)?;
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.push(Value::Bool(is_equal(left, right)));
+                }
+                OpCode::Greater => self.comparison(|a, b| a > b)?,
+                OpCode::GreaterEqual => self.comparison(|a, b| a >= b)?,
+                OpCode::Less => self.comparison(|a, b| a < b)?,
+                OpCode::LessEqual => self.comparison(|a, b| a <= b)?,
+                OpCode::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    match (left, right) {
+                        (Value::Number(a), Value::Number(b)) => self.push(Value::Number(a + b)),
+                        (Value::String(a), Value::String(b)) => {
+                            self.push(Value::String(Rc::from(a.to_string() + &b)))
+                        }
+                        (left, right) => {
+                            let error =
+                                self.type_mismatch("Operands must be two numbers or two strings.");
+                            return Err(self.blame(error, vec![(0, &left), (1, &right)]));
+                        }
+                    }
+                }
+                OpCode::Subtract => self.arithmetic(|a, b| a - b)?,
+                OpCode::Multiply => self.arithmetic(|a, b| a * b)?,
+                OpCode::Divide => self.arithmetic(|a, b| a / b)?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.push(Value::Bool(!is_truthy(value)));
+                }
+                OpCode::Negate => match self.pop() {
+                    Value::Number(n) => self.push(Value::Number(-n)),
+                    value => {
+                        let error = self.type_mismatch("Operand must be a number.");
+                        return Err(self.blame(error, vec![(0, &value)]));
+                    }
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    writeln!(self.output.borrow_mut(), "{}", value)?;
+                }
+                OpCode::Interpolate => {
+                    let count = self.read_u16() as usize;
+                    let parts = self.stack.split_off(self.stack.len() - count);
+                    let text: String = parts.iter().map(Value::to_string).collect();
+                    self.push(Value::String(Rc::from(text)));
+                }
+                OpCode::BuildList => {
+                    let count = self.read_u16() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.push(Value::List(Rc::new(RefCell::new(elements))));
+                }
+                OpCode::BuildMap => {
+                    let count = self.read_u16() as usize;
+                    let entries = self.stack.split_off(self.stack.len() - count * 2);
+                    let mut map = LoxMap::new();
+                    for (i, entry) in entries.chunks(2).enumerate() {
+                        let key = Key::new(&entry[0])
+                            .map_err(|kind| self.blame(self.error(kind), vec![(i, &entry[0])]))?;
+                        map.insert(key, entry[1].clone());
+                    }
+                    self.push(Value::Map(Rc::new(RefCell::new(map))));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let value = object
+                        .get_index(&index)
+                        .map_err(|kind| self.index_error(kind, &object, &index))?;
+                    self.push(value);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let object = self.pop();
+                    object
+                        .set_index(&index, value.clone())
+                        .map_err(|kind| self.index_error(kind, &object, &index))?;
+                    self.push(value);
+                }
+                OpCode::Jump => {
+                    let distance = self.read_u16() as usize;
+                    self.frame_mut().ip += distance;
+                }
+                OpCode::JumpIfFalse => {
+                    let distance = self.read_u16() as usize;
+                    if !is_truthy(self.peek(0).clone()) {
+                        self.frame_mut().ip += distance;
+                    }
+                }
+                OpCode::Loop => {
+                    let distance = self.read_u16() as usize;
+                    self.frame_mut().ip -= distance;
+                }
+                OpCode::Call => {
+                    let argument_count = self.read_byte() as usize;
+                    let callee = self.peek(argument_count).clone();
+                    self.call_value(callee, argument_count)?;
+                }
+                OpCode::Closure => {
+                    let function = match self.read_constant() {
+                        Value::Object(Object::Function(function)) => function,
+                        _ => {
+                            return Err(self.type_mismatch("Can only make closures from functions."))
+                        }
+                    };
+                    let mut closure = Closure::new(function);
+                    for _ in 0..closure.function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = if is_local {
+                            self.capture_upvalue(self.frame().slots + index)
+                        } else {
+                            Rc::clone(&self.frame().closure.upvalues[index])
+                        };
+                        closure.upvalues.push(upvalue);
+                    }
+                    self.push(Value::Object(Object::Closure(Rc::new(closure))));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(result);
+                    }
+                    self.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_name();
+                    let class = Class::new(name.to_string());
+                    self.push(Value::Object(Object::Class(Rc::new(RefCell::new(class)))));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::Object(Object::Class(class)) => Rc::clone(class),
+                        _ => return Err(self.type_mismatch("Superclass must be a class.")),
+                    };
+                    let subclass = match self.pop() {
+                        Value::Object(Object::Class(class)) => class,
+                        _ => return Err(self.type_mismatch("Only classes can inherit.")),
+                    };
+                    // copy-down inheritance: methods declared in the subclass
+                    // are added afterwards, so they win
+                    let methods = superclass.borrow().methods.clone();
+                    subclass.borrow_mut().methods.extend(methods);
+                }
+                OpCode::Method => {
+                    let name = self.read_name();
+                    let method = match self.pop() {
+                        Value::Object(Object::Closure(closure)) => closure,
+                        _ => return Err(self.type_mismatch("Methods must be functions.")),
+                    };
+                    match self.peek(0) {
+                        Value::Object(Object::Class(class)) => {
+                            class.borrow_mut().methods.insert(name, method);
+                        }
+                        _ => return Err(self.type_mismatch("Only classes have methods.")),
+                    }
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, callee: Value, argument_count: usize) -> Result<(), LoxError> {
+        match callee {
+            Value::Object(Object::Closure(closure)) => self.call(closure, argument_count),
+            Value::Object(Object::BoundMethod(bound)) => {
+                let slot = self.stack.len() - argument_count - 1;
+                self.stack[slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), argument_count)
+            }
+            Value::Native(native) => {
+                if argument_count != native.arity {
+                    return Err(self.arity_mismatch(native.arity, argument_count));
+                }
+                let arguments = self.stack.split_off(self.stack.len() - argument_count);
+                let result = native.call(&arguments).map_err(|kind| self.error(kind))?;
+                self.pop();
+                self.push(result);
+                Ok(())
+            }
+            Value::Object(Object::Class(class)) => {
+                let slot = self.stack.len() - argument_count - 1;
+                let instance = Instance::new(Rc::clone(&class));
+                self.stack[slot] = Value::Object(Object::Instance(Rc::new(RefCell::new(instance))));
+
+                let initializer = class.borrow().methods.get(&Symbol::INIT).cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, argument_count),
+                    None if argument_count != 0 => Err(self.arity_mismatch(0, argument_count)),
+                    None => Ok(()),
+                }
+            }
+            _ => Err(self.type_mismatch("Can only call functions and classes.")),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, argument_count: usize) -> Result<(), LoxError> {
+        if argument_count != closure.function.arity {
+            return Err(self.arity_mismatch(closure.function.arity, argument_count));
+        }
+        if self.frames.len() == FRAMES_MAX {
+            return Err(self.error(RuntimeErrorKind::StackOverflow));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - argument_count - 1,
+        });
+        Ok(())
+    }
+
+    /// Replace the instance on top of the stack with its method `name`, bound to it.
+    fn bind_method(&mut self, class: &Rc<RefCell<Class>>, name: Symbol) -> Result<(), LoxError> {
+        let method = match class.borrow().methods.get(&name) {
+            Some(method) => Rc::clone(method),
+            None => {
+                let kind = RuntimeErrorKind::UndefinedProperty(name.to_string());
+                return Err(self.error(kind));
+            }
+        };
+        let receiver = self.pop();
+        let bound = BoundMethod { receiver, method };
+        self.push(Value::Object(Object::BoundMethod(Rc::new(bound))));
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot));
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    /// Move every variable at or above `from` on the stack into the upvalues
+    /// that point at it.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) => slot,
+                Upvalue::Closed(_) => return false,
+            };
+            if slot < from {
+                return true;
+            }
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+            false
+        });
+    }
+
+    fn arithmetic(&mut self, op: fn(f64, f64) -> f64) -> Result<(), LoxError> {
+        let (a, b) = self.number_operands()?;
+        self.push(Value::Number(op(a, b)));
+        Ok(())
+    }
+
+    fn comparison(&mut self, op: fn(f64, f64) -> bool) -> Result<(), LoxError> {
+        let (a, b) = self.number_operands()?;
+        self.push(Value::Bool(op(a, b)));
+        Ok(())
+    }
+
+    fn number_operands(&mut self) -> Result<(f64, f64), LoxError> {
+        let right = self.pop();
+        let left = self.pop();
+        match (left, right) {
+            (Value::Number(a), Value::Number(b)) => Ok((a, b)),
+            (left, right) => {
+                let error = self.type_mismatch("Operands must be numbers.");
+                let culprits = vec![(0, &left), (1, &right)];
+                let culprits = culprits.into_iter().filter(|(_, value)| !value.is_number());
+                Err(self.blame(error, culprits.collect()))
+            }
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn chunk(&self) -> &Chunk {
+        &self.frame().closure.function.chunk
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.chunk().constants[index].clone()
+    }
+
+    fn read_name(&mut self) -> Symbol {
+        let index = self.read_u16() as usize;
+        self.chunk().names[index]
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the stack never underflows")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    /// An error blamed on the instruction that's running right now.
+    fn error(&self, kind: RuntimeErrorKind) -> LoxError {
+        let frame = self.frame();
+        let position = &frame.closure.function.chunk.positions[frame.ip - 1];
+        LoxError::runtime(&position.token(), kind)
+    }
+
+    /// Point an error at the operands of the instruction that's running which
+    /// are to blame for it, saying what each one turned out to be. They're
+    /// numbered in the order they were pushed.
+    fn blame(&self, error: LoxError, culprits: Vec<(usize, &Value)>) -> LoxError {
+        let frame = self.frame();
+        let spans = frame.closure.function.chunk.operands_at(frame.ip - 1);
+        culprits
+            .into_iter()
+            .fold(error, |error, (operand, value)| match spans.get(operand) {
+                Some(&span) => error.with_label(span, format!("this is {}", value.describe())),
+                None => error,
+            })
+    }
+
+    fn index_error(&self, kind: RuntimeErrorKind, object: &Value, index: &Value) -> LoxError {
+        let culprit = index_culprit(object, &kind);
+        let error = self.error(kind);
+        match culprit {
+            Some(operand) => self.blame(error, vec![(operand, [object, index][operand])]),
+            None => error,
+        }
+    }
+
+    fn type_mismatch(&self, message: &'static str) -> LoxError {
+        self.error(RuntimeErrorKind::TypeMismatch(message))
+    }
+
+    fn arity_mismatch(&self, expected: usize, found: usize) -> LoxError {
+        self.error(RuntimeErrorKind::ArityMismatch { expected, found })
+    }
+
+    fn undefined_variable(&self, name: Symbol) -> LoxError {
+        let help = name
+            .with_str(suggestion)
+            .map(|lox| format!("did you mean `{}`?", lox));
+        let error = self.error(RuntimeErrorKind::UndefinedVariable(name.to_string()));
+        match help {
+            Some(help) => error.with_help(help),
+            None => error,
+        }
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,12 @@
+use crate::{error::LoxError, interpreter::Interpreter, literal::Value};
+use std::fmt::{Debug, Display};
+
+/// Anything that can be called from Lox code with `thing(args)`.
+pub trait Callable: Debug + Display {
+    /// How many arguments this expects. Calls with any other count are a runtime error.
+    fn arity(&self) -> usize;
+
+    /// Do the thing! `arguments` has already been checked against `arity`.
+    fn call(&self, interpreter: &mut Interpreter, arguments: Vec<Value>)
+        -> Result<Value, LoxError>;
+}
@@ -0,0 +1,115 @@
+use crate::{
+    callable::Callable,
+    error::{LoxError, RuntimeErrorKind},
+    function::LoxFunction,
+    interpreter::Interpreter,
+    literal::Value,
+    symbol::Symbol,
+    token::Token,
+};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+/// A class declared in Lox code. Calling one makes a new `LoxInstance`.
+#[derive(Debug)]
+pub struct LoxClass {
+    pub name: Symbol,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<Symbol, Rc<LoxFunction>>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: Symbol,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<Symbol, Rc<LoxFunction>>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Look for a method on this class, then up the inheritance chain.
+    pub fn find_method(&self, name: Symbol) -> Option<Rc<LoxFunction>> {
+        match self.methods.get(&name) {
+            Some(method) => Some(Rc::clone(method)),
+            None => self
+                .superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    /// Takes as many arguments as `init` does, or none if there isn't one.
+    pub fn arity(&self) -> usize {
+        self.find_method(Symbol::INIT)
+            .map_or(0, |init| init.arity())
+    }
+
+    /// Make a new instance, running `init` on it if the class has one.
+    ///
+    /// This lives here rather than in a `Callable` impl because the instance
+    /// needs a handle to the class itself, not just a borrow of it.
+    pub fn instantiate(
+        class: &Rc<LoxClass>,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(class))));
+        if let Some(initializer) = class.find_method(Symbol::INIT) {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, arguments)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl Display for LoxClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An object made from a `LoxClass`, with its own set of fields.
+#[derive(Debug)]
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<Symbol, Value>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Fields shadow methods. Methods come back bound to `instance`, so `this` works.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Value, LoxError> {
+        if let Some(value) = instance.borrow().fields.get(&name.name()) {
+            return Ok(value.clone());
+        }
+
+        let method = instance.borrow().class.find_method(name.name());
+        match method {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(Rc::clone(instance))))),
+            None => Err(LoxError::runtime(
+                name,
+                RuntimeErrorKind::UndefinedProperty(name.lexeme.to_string()),
+            )),
+        }
+    }
+
+    pub fn set(&mut self, name: &Token, value: Value) {
+        self.fields.insert(name.name(), value);
+    }
+}
+
+impl Display for LoxInstance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{error::LoxError, token::Span};
+use std::fmt::Write;
+
+/// A bit of source code worth pointing at, with a note about why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new<S: Into<String>>(span: Span, message: S) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// Extra context for an error, on top of what went wrong and where.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Notes {
+    /// Other places in the source code that have something to do with the error.
+    pub labels: Vec<Label>,
+    /// Suggestions for how to fix it.
+    pub help: Vec<String>,
+}
+
+/// Whether to dress up rendered diagnostics with ANSI colors.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Style {
+    Plain,
+    Colored,
+}
+
+/// One error, ready to be shown to a human alongside the code it's about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    /// Where the error actually is. Not every error has a location.
+    pub primary: Option<Label>,
+    pub notes: Notes,
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+impl Diagnostic {
+    /// Render this diagnostic like so:
+    ///
+    /// ```text
+    /// error: Expect ';' after expression.
+    ///  --> script.lox:1:5
+    ///   |
+    /// 1 | let x = 1;
+    ///   |     ^
+    ///   | --- this is a variable called `let`
+    ///   |
+    ///   = help: did you mean `var`?
+    /// ```
+    ///
+    /// `name` is whatever should be shown as the file the source came from.
+    pub fn render(&self, name: &str, source: &str, style: Style) -> String {
+        let paint = |color: &'static str| match style {
+            Style::Plain => "",
+            Style::Colored => color,
+        };
+        let (bold, red, blue, cyan, reset) = (
+            paint(BOLD),
+            paint(RED),
+            paint(BLUE),
+            paint(CYAN),
+            paint(RESET),
+        );
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{}error{}{}: {}{}",
+            red, reset, bold, self.message, reset
+        )
+        .unwrap();
+
+        // primary label first, then the rest in source order. labels that don't
+        // point into `source` must be about some other code, so they're left out
+        let primary = self
+            .primary
+            .as_ref()
+            .filter(|label| within(source, label.span));
+        let mut labels: Vec<(&Label, bool)> = primary.iter().map(|&l| (l, true)).collect();
+        let mut secondary: Vec<_> = self
+            .notes
+            .labels
+            .iter()
+            .filter(|label| within(source, label.span))
+            .map(|l| (l, false))
+            .collect();
+        secondary.sort_by_key(|(label, _)| label.span.start);
+        labels.extend(secondary);
+
+        let gutter = labels
+            .iter()
+            .map(|(label, _)| location(source, label.span.start).0)
+            .max()
+            .map_or(0, |line| line.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        if let Some(primary) = primary {
+            let (line, column) = location(source, primary.span.start);
+            writeln!(
+                out,
+                "{}{}-->{} {}:{}:{}",
+                pad, blue, reset, name, line, column
+            )
+            .unwrap();
+        }
+
+        if !labels.is_empty() {
+            writeln!(out, "{} {}|{}", pad, blue, reset).unwrap();
+
+            // every line with a label on it, in order, each shown once
+            let mut lines: Vec<usize> = labels
+                .iter()
+                .map(|(label, _)| location(source, label.span.start).0)
+                .collect();
+            lines.sort_unstable();
+            lines.dedup();
+
+            let mut previous = None;
+            for line in lines {
+                if let Some(previous) = previous {
+                    if line > previous + 1 {
+                        writeln!(out, "{}...{}", blue, reset).unwrap();
+                    }
+                }
+                previous = Some(line);
+
+                let text = source.lines().nth(line - 1).unwrap_or("");
+                writeln!(
+                    out,
+                    "{}{:>width$} |{} {}",
+                    blue,
+                    line,
+                    reset,
+                    text,
+                    width = gutter
+                )
+                .unwrap();
+
+                for (label, is_primary) in &labels {
+                    if location(source, label.span.start).0 != line {
+                        continue;
+                    }
+                    let (column, width) = underline(source, label.span);
+                    // reuse the line's own whitespace so tabs line up
+                    let indent: String = text
+                        .chars()
+                        .take(column - 1)
+                        .map(|c| if c == '\t' { '\t' } else { ' ' })
+                        .collect();
+                    let (mark, color) = if *is_primary { ('^', red) } else { ('-', blue) };
+                    let marks: String = mark.to_string().repeat(width);
+                    let message = if label.message.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" {}", label.message)
+                    };
+                    writeln!(
+                        out,
+                        "{} {}|{} {}{}{}{}{}",
+                        pad, blue, reset, indent, color, marks, message, reset
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        if !self.notes.help.is_empty() {
+            if !labels.is_empty() {
+                writeln!(out, "{} {}|{}", pad, blue, reset).unwrap();
+            }
+            for help in &self.notes.help {
+                writeln!(
+                    out,
+                    "{} {}={} {}help{}: {}",
+                    pad, blue, reset, cyan, reset, help
+                )
+                .unwrap();
+            }
+        }
+
+        out
+    }
+}
+
+impl LoxError {
+    /// Turn this error into diagnostics, one per problem.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let (message, notes) = match self {
+            LoxError::Static(errors) => {
+                return errors.iter().flat_map(LoxError::diagnostics).collect()
+            }
+            LoxError::Io(error) => (error.to_string(), Notes::default()),
+            LoxError::Lex { message, notes, .. }
+            | LoxError::Parse { message, notes, .. }
+            | LoxError::Resolve { message, notes, .. }
+            | LoxError::Compile { message, notes, .. } => (message.clone(), notes.as_ref().clone()),
+            LoxError::Runtime { kind, notes, .. } => (kind.to_string(), notes.as_ref().clone()),
+        };
+        vec![Diagnostic {
+            message,
+            primary: self.span().map(|span| Label::new(span, "")),
+            notes,
+        }]
+    }
+}
+
+/// Words from other languages that people reach for out of habit, and what
+/// Lox calls them.
+pub fn suggestion(word: &str) -> Option<&'static str> {
+    match word {
+        "let" | "const" | "val" | "mut" => Some("var"),
+        "function" | "func" | "fn" | "def" => Some("fun"),
+        "elif" | "elsif" => Some("else if"),
+        "null" | "None" | "undefined" | "nul" => Some("nil"),
+        "self" => Some("this"),
+        "True" => Some("true"),
+        "False" => Some("false"),
+        "&&" | "&" => Some("and"),
+        "||" | "|" => Some("or"),
+        "echo" | "puts" => Some("print"),
+        _ => None,
+    }
+}
+
+/// Whether `span` covers whole characters of `source`.
+fn within(source: &str, span: Span) -> bool {
+    span.start <= span.end
+        && span.end <= source.len()
+        && source.is_char_boundary(span.start)
+        && source.is_char_boundary(span.end)
+}
+
+/// The nearest offset at or before `offset` that's at the start of a
+/// character in `source`, so it's always safe to slice there.
+fn clamp(source: &str, offset: usize) -> usize {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// The 1-based line and column (in chars) of a byte offset.
+fn location(source: &str, offset: usize) -> (usize, usize) {
+    let offset = clamp(source, offset);
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = source[line_start..offset].chars().count() + 1;
+    (line, column)
+}
+
+/// Where to start underlining `span`, and how many chars to underline. Spans
+/// that run over several lines only get underlined on the first one.
+fn underline(source: &str, span: Span) -> (usize, usize) {
+    let (_, column) = location(source, span.start);
+    let start = clamp(source, span.start);
+    let end = clamp(source, span.end).max(start);
+    let width = source[start..end]
+        .split('\n')
+        .next()
+        .map_or(0, |first| first.chars().count());
+    // zero-width spans (like the end of the file) still get a caret
+    (column, width.max(1))
+}
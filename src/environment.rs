@@ -1,57 +1,89 @@
+use crate::diagnostic::suggestion;
+use crate::error::{LoxError, RuntimeErrorKind};
 use crate::literal::Value;
+use crate::symbol::Symbol;
 use crate::token::Token;
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-#[derive(Debug)]
-pub struct Environment<'enc> {
-    values: HashMap<String, Value>,
-    enclosing: Option<&'enc mut Environment<'enc>>,
+/// One scope's worth of variables, plus a link to the scope that encloses it.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<Symbol, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
-impl<'enc> Environment<'enc> {
-    pub fn new(enclosing: Option<&'enc mut Environment<'enc>>) -> Self {
+impl Environment {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment>>>) -> Self {
         Self {
             values: HashMap::new(),
             enclosing,
         }
     }
-    pub fn define<S: Into<String>>(&mut self, name: S, value: Value) {
+
+    pub fn define<S: Into<Symbol>>(&mut self, name: S, value: Value) {
         self.values.insert(name.into(), value);
     }
 
-    pub fn get(&mut self, name: &Token) -> Result<Value, (Token, String)> {
-        if self.values.contains_key(&name.lexeme) {
-            Ok(self.values.get(&name.lexeme).unwrap().clone())
-        } else if let Some(ref mut enclosing) = self.enclosing {
-            enclosing.get(name)
+    pub fn get(&self, name: &Token) -> Result<Value, LoxError> {
+        self.lookup(name.name())
+            .ok_or_else(|| undefined_variable(name))
+    }
+
+    /// Find a variable by name, walking out through the enclosing scopes.
+    pub fn lookup(&self, name: Symbol) -> Option<Value> {
+        match self.values.get(&name) {
+            Some(value) => Some(value.clone()),
+            None => self
+                .enclosing
+                .as_ref()
+                .and_then(|enclosing| enclosing.borrow().lookup(name)),
+        }
+    }
+
+    /// Like `get`, but goes straight to the scope `distance` steps out, as
+    /// worked out by the resolver.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Value, LoxError> {
+        self.lookup_at(distance, name.name())
+            .ok_or_else(|| undefined_variable(name))
+    }
+
+    pub fn lookup_at(&self, distance: usize, name: Symbol) -> Option<Value> {
+        if distance == 0 {
+            self.values.get(&name).cloned()
         } else {
-            Err((
-                name.clone(),
-                format!("Undefined variable '{}'.", name.lexeme),
-            ))
+            self.enclosing
+                .as_ref()
+                .and_then(|enclosing| enclosing.borrow().lookup_at(distance - 1, name))
+        }
+    }
+
+    pub fn assign_at(&mut self, distance: usize, name: &Token, value: &Value) {
+        if distance == 0 {
+            self.values.insert(name.name(), value.clone());
+        } else if let Some(ref enclosing) = self.enclosing {
+            enclosing.borrow_mut().assign_at(distance - 1, name, value);
         }
     }
 
-    pub fn assign(&mut self, name: &Token, value: &Value) -> Result<(), (Token, String)> {
-        if self.values.contains_key(&name.lexeme) {
-            self.values.insert(name.lexeme.clone(), value.clone());
+    pub fn assign(&mut self, name: &Token, value: &Value) -> Result<(), LoxError> {
+        if let Some(slot) = self.values.get_mut(&name.name()) {
+            *slot = value.clone();
             Ok(())
-        } else if let Some(ref mut enclosing) = self.enclosing {
-            enclosing.assign(name, value)
+        } else if let Some(ref enclosing) = self.enclosing {
+            enclosing.borrow_mut().assign(name, value)
         } else {
-            Err((
-                name.clone(),
-                format!("Undefined variable '{}'.", name.lexeme),
-            ))
+            Err(undefined_variable(name))
         }
     }
 }
 
-impl<'enc> Default for Environment<'enc> {
-    fn default() -> Self {
-        Self {
-            values: HashMap::new(),
-            enclosing: None,
-        }
+fn undefined_variable(name: &Token) -> LoxError {
+    let error = LoxError::runtime(
+        name,
+        RuntimeErrorKind::UndefinedVariable(name.lexeme.to_string()),
+    );
+    match name.lexeme.with_str(suggestion) {
+        Some(lox) => error.with_help(format!("did you mean `{}`?", lox)),
+        None => error,
     }
 }
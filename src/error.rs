@@ -0,0 +1,234 @@
+use crate::{
+    diagnostic::{Label, Notes},
+    literal::format_number,
+    token::{Span, Token},
+    token_type::TokenType,
+};
+use std::{error::Error, fmt::Display, io};
+
+/// Everything that can go wrong with a Lox program, from reading it in to running it.
+#[derive(Debug)]
+pub enum LoxError {
+    /// The script couldn't be read in the first place.
+    Io(io::Error),
+    /// The scanner found something it couldn't turn into a token.
+    Lex {
+        line: usize,
+        span: Span,
+        message: String,
+        notes: Box<Notes>,
+    },
+    /// The parser found tokens it couldn't make sense of.
+    Parse {
+        token: Token,
+        message: String,
+        notes: Box<Notes>,
+    },
+    /// The resolver found a mistake that doesn't need the program to run to spot.
+    Resolve {
+        token: Token,
+        message: String,
+        notes: Box<Notes>,
+    },
+    /// The program was fine, but doesn't fit in the bytecode format.
+    Compile {
+        token: Token,
+        message: String,
+        notes: Box<Notes>,
+    },
+    /// The script blew up while it was running.
+    Runtime {
+        token: Token,
+        kind: RuntimeErrorKind,
+        notes: Box<Notes>,
+    },
+    /// Every lex, parse and resolve error in a script, in the order they were found.
+    Static(Vec<LoxError>),
+}
+
+/// The different ways a running Lox program can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorKind {
+    /// A value was the wrong type for what was done with it. Holds the full
+    /// explanation, like `Operand must be a number.`
+    TypeMismatch(&'static str),
+    /// Tried to use a variable that was never defined. Holds its name.
+    UndefinedVariable(String),
+    /// Tried to get a property an instance doesn't have. Holds its name.
+    UndefinedProperty(String),
+    /// Called a function with the wrong number of arguments.
+    ArityMismatch { expected: usize, found: usize },
+    /// Calls to Lox functions nested deeper than either backend allows.
+    StackOverflow,
+    /// Indexed a list outside of its elements.
+    IndexOutOfBounds { index: f64, length: usize },
+    /// Looked up a key a map doesn't have. Holds the key.
+    UndefinedKey(String),
+    /// Tried to take an element off a list with none left.
+    EmptyList,
+    /// A native function failed. Holds its explanation.
+    Native(String),
+}
+
+impl LoxError {
+    pub fn runtime(token: &Token, kind: RuntimeErrorKind) -> Self {
+        LoxError::Runtime {
+            token: token.clone(),
+            kind,
+            notes: Box::default(),
+        }
+    }
+
+    /// Point out another bit of source code that has something to do with this error.
+    pub fn with_label<S: Into<String>>(mut self, span: Span, message: S) -> Self {
+        if let Some(notes) = self.notes_mut() {
+            notes.labels.push(Label::new(span, message));
+        }
+        self
+    }
+
+    /// Add a suggestion for how to fix this error.
+    pub fn with_help<S: Into<String>>(mut self, help: S) -> Self {
+        if let Some(notes) = self.notes_mut() {
+            notes.help.push(help.into());
+        }
+        self
+    }
+
+    fn notes_mut(&mut self) -> Option<&mut Notes> {
+        match self {
+            LoxError::Lex { notes, .. }
+            | LoxError::Parse { notes, .. }
+            | LoxError::Resolve { notes, .. }
+            | LoxError::Compile { notes, .. }
+            | LoxError::Runtime { notes, .. } => Some(notes.as_mut()),
+            LoxError::Io(_) | LoxError::Static(_) => None,
+        }
+    }
+
+    /// The line the problem is on, if it's down to a particular bit of source code.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            LoxError::Lex { line, .. } => Some(*line),
+            LoxError::Parse { token, .. }
+            | LoxError::Resolve { token, .. }
+            | LoxError::Compile { token, .. }
+            | LoxError::Runtime { token, .. } => Some(token.line),
+            LoxError::Io(_) | LoxError::Static(_) => None,
+        }
+    }
+
+    /// Exactly which bit of source code the problem is with, if any.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            LoxError::Lex { span, .. } => Some(*span),
+            LoxError::Parse { token, .. }
+            | LoxError::Resolve { token, .. }
+            | LoxError::Compile { token, .. }
+            | LoxError::Runtime { token, .. } => Some(token.span),
+            LoxError::Io(_) | LoxError::Static(_) => None,
+        }
+    }
+
+    /// The exit status `jlox` finishes with because of this error. Like the
+    /// reference jlox, it follows sysexits.h: 65 for mistakes in the code
+    /// and 70 for errors while running it.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            LoxError::Io(_) => 1,
+            LoxError::Lex { .. }
+            | LoxError::Parse { .. }
+            | LoxError::Resolve { .. }
+            | LoxError::Compile { .. }
+            | LoxError::Static(_) => 65,
+            LoxError::Runtime { .. } => 70,
+        }
+    }
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Io(error) => write!(f, "{}", error),
+            LoxError::Lex { line, message, .. } => {
+                write!(f, "[line {}] Error: {}", line, message)
+            }
+            LoxError::Parse { token, message, .. }
+            | LoxError::Resolve { token, message, .. }
+            | LoxError::Compile { token, message, .. } => match token.kind {
+                TokenType::EOF => write!(f, "[line {}] Error at end: {}", token.line, message),
+                _ => write!(
+                    f,
+                    "[line {}] Error at '{}': {}",
+                    token.line, token.lexeme, message
+                ),
+            },
+            LoxError::Runtime { token, kind, .. } => {
+                write!(f, "{}\n[line {}]", kind, token.line)
+            }
+            LoxError::Static(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for LoxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoxError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoxError {
+    fn from(error: io::Error) -> Self {
+        LoxError::Io(error)
+    }
+}
+
+impl From<String> for RuntimeErrorKind {
+    fn from(message: String) -> Self {
+        RuntimeErrorKind::Native(message)
+    }
+}
+
+impl From<&str> for RuntimeErrorKind {
+    fn from(message: &str) -> Self {
+        RuntimeErrorKind::Native(message.to_string())
+    }
+}
+
+impl Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeErrorKind::TypeMismatch(message) => write!(f, "{}", message),
+            RuntimeErrorKind::UndefinedVariable(name) => {
+                write!(f, "Undefined variable '{}'.", name)
+            }
+            RuntimeErrorKind::UndefinedProperty(name) => {
+                write!(f, "Undefined property '{}'.", name)
+            }
+            RuntimeErrorKind::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} arguments but got {}.", expected, found)
+            }
+            RuntimeErrorKind::StackOverflow => write!(f, "Stack overflow."),
+            RuntimeErrorKind::IndexOutOfBounds { index, length } => write!(
+                f,
+                "Index {} is out of bounds for a list of length {}.",
+                format_number(*index),
+                length
+            ),
+            RuntimeErrorKind::UndefinedKey(key) => write!(f, "Undefined key '{}'.", key),
+            RuntimeErrorKind::EmptyList => write!(f, "Can't pop from an empty list."),
+            RuntimeErrorKind::Native(message) => write!(f, "{}", message),
+        }
+    }
+}
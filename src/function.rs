@@ -0,0 +1,96 @@
+use crate::{
+    ast::stmt::Stmt,
+    callable::Callable,
+    class::LoxInstance,
+    environment::Environment,
+    error::LoxError,
+    interpreter::{Interpreter, Unwind},
+    literal::Value,
+    symbol::Symbol,
+    token::Token,
+};
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+/// A function declared in Lox code, along with the scope it was declared in.
+#[derive(Debug)]
+pub struct LoxFunction {
+    name: Token,
+    params: Vec<Token>,
+    body: Rc<Vec<Stmt>>,
+    /// The environment that was live when the function was declared. Keeping
+    /// it alive here is what makes closures work.
+    closure: Rc<RefCell<Environment>>,
+    /// `init` methods always hand back `this`, whatever they return.
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        name: Token,
+        params: Vec<Token>,
+        body: Rc<Vec<Stmt>>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            body,
+            closure,
+            is_initializer,
+        }
+    }
+
+    /// Make a copy of this method where `this` refers to `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        environment.define(Symbol::THIS, Value::Instance(instance));
+        LoxFunction::new(
+            self.name.clone(),
+            self.params.clone(),
+            Rc::clone(&self.body),
+            Rc::new(RefCell::new(environment)),
+            self.is_initializer,
+        )
+    }
+}
+
+impl Callable for LoxFunction {
+    fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        // every call gets a fresh scope, so recursion doesn't trample on itself
+        let mut environment = Environment::new(Some(Rc::clone(&self.closure)));
+        for (param, argument) in self.params.iter().zip(arguments) {
+            environment.define(param.name(), argument);
+        }
+
+        let value = match interpreter.execute_block(&self.body, environment) {
+            Ok(_) => Value::Nil,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+        };
+
+        if self.is_initializer {
+            Ok(self
+                .closure
+                .borrow()
+                .lookup_at(0, Symbol::THIS)
+                .expect("initializer wasn't bound to an instance"))
+        } else {
+            Ok(value)
+        }
+    }
+}
+
+impl Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
+}
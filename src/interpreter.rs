@@ -3,125 +3,453 @@ use crate::{
         expr::{self, Expr},
         stmt,
     },
+    class::{LoxClass, LoxInstance},
     environment::Environment,
-    literal::Value,
+    error::{LoxError, RuntimeErrorKind},
+    function::LoxFunction,
+    literal::{Key, Value},
+    map::LoxMap,
+    native,
+    symbol::Symbol,
     token::Token,
     token_type::TokenType,
+    Output,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io,
+    rc::Rc,
 };
 use stmt::Stmt;
 
-pub struct Interpreter<'i> {
-    environment: Environment<'i>,
+/// How deep calls to Lox functions can nest before it's a stack overflow. The
+/// VM stops at the same depth, so the backends agree on which programs recurse
+/// too far.
+pub const MAX_CALL_DEPTH: usize = 4095;
+
+pub struct Interpreter {
+    /// The outermost scope. Anything the resolver didn't find locally lives here.
+    globals: Rc<RefCell<Environment>>,
+    /// The innermost scope at this point in execution.
+    environment: Rc<RefCell<Environment>>,
+    /// Where `print` goes.
+    output: Output,
+    /// How many calls to Lox functions are running right now.
+    depth: usize,
 }
 
-impl<'i> expr::Visitor<Result<Value, (Token, String)>> for Interpreter<'i> {
-    fn visit_binary_expr(
-        &mut self,
-        a0: &Expr,
-        a1: &Token,
-        a2: &Expr,
-    ) -> Result<Value, (Token, String)> {
+/// Why execution stopped partway through a statement.
+#[derive(Debug)]
+pub enum Unwind {
+    /// Something went wrong, and it's heading all the way up to the user.
+    Error(LoxError),
+    /// A `return` statement, heading back up to the function call it's in.
+    Return(Value),
+}
+
+impl From<LoxError> for Unwind {
+    fn from(error: LoxError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+impl expr::Visitor<Result<Value, Unwind>> for Interpreter {
+    fn visit_binary_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> Result<Value, Unwind> {
         let left = self.evaluate(a0)?;
         let right = self.evaluate(a2)?;
 
         match a1.kind {
             TokenType::GREATER => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Bool(left.unwrap_number() > right.unwrap_number()))
             }
             TokenType::GREATER_EQUAL => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Bool(left.unwrap_number() >= right.unwrap_number()))
             }
             TokenType::LESS => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Bool(left.unwrap_number() < right.unwrap_number()))
             }
             TokenType::LESS_EQUAL => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Bool(left.unwrap_number() <= right.unwrap_number()))
             }
             TokenType::BANG_EQUAL => Ok(Value::Bool(!is_equal(left, right))),
             TokenType::EQUAL_EQUAL => Ok(Value::Bool(is_equal(left, right))),
             TokenType::MINUS => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Number(left.unwrap_number() - right.unwrap_number()))
             }
             TokenType::SLASH => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Number(left.unwrap_number() / right.unwrap_number()))
             }
             TokenType::STAR => {
-                check_number_operands(a1, &left, &right)?;
+                check_number_operands(a1, (a0, &left), (a2, &right))?;
                 Ok(Value::Number(left.unwrap_number() * right.unwrap_number()))
             }
             TokenType::PLUS => {
                 if left.is_number() && right.is_number() {
                     Ok(Value::Number(left.unwrap_number() + right.unwrap_number()))
                 } else if left.is_string() && right.is_string() {
-                    Ok(Value::String(
-                        left.unwrap_string().to_string() + right.unwrap_string(),
-                    ))
+                    let s = left.unwrap_string().to_string() + right.unwrap_string();
+                    Ok(Value::String(Rc::from(s)))
                 } else {
-                    Err((
-                        a1.clone(),
-                        "Operands must be two numbers or two strings.".to_string(),
-                    ))
+                    let error = type_mismatch(a1, "Operands must be two numbers or two strings.")
+                        .with_label(a0.span(), format!("this is {}", left.describe()))
+                        .with_label(a2.span(), format!("this is {}", right.describe()));
+                    Err(error.into())
                 }
             }
             _ => unreachable!(),
         }
     }
-    fn visit_grouping_expr(&mut self, a0: &Expr) -> Result<Value, (Token, String)> {
+    fn visit_call_expr(&mut self, a0: &Expr, a1: &Token, a2: &[Expr]) -> Result<Value, Unwind> {
+        let callee = self.evaluate(a0)?;
+
+        let mut arguments = Vec::with_capacity(a2.len());
+        for argument in a2 {
+            arguments.push(self.evaluate(argument)?);
+        }
+
+        let arity = match &callee {
+            Value::Callable(function) => function.arity(),
+            Value::Class(class) => class.arity(),
+            Value::Native(native) => native.arity,
+            _ => return Err(type_mismatch(a1, "Can only call functions and classes.").into()),
+        };
+
+        if arguments.len() != arity {
+            return Err(LoxError::runtime(
+                a1,
+                RuntimeErrorKind::ArityMismatch {
+                    expected: arity,
+                    found: arguments.len(),
+                },
+            )
+            .into());
+        }
+
+        // every Lox call goes a few levels deeper into the Rust stack, so stop
+        // before that runs out. Natives and classes without an `init` don't run
+        // any Lox code, so they don't count.
+        let runs_lox_code = match &callee {
+            Value::Callable(_) => true,
+            Value::Class(class) => class.find_method(Symbol::INIT).is_some(),
+            _ => false,
+        };
+        if runs_lox_code && self.depth == MAX_CALL_DEPTH {
+            return Err(LoxError::runtime(a1, RuntimeErrorKind::StackOverflow).into());
+        }
+
+        self.depth += 1;
+        let result = match callee {
+            Value::Callable(function) => function.call(self, arguments),
+            Value::Class(class) => LoxClass::instantiate(&class, self, arguments),
+            Value::Native(native) => {
+                // errors from natives point at the call, since there's no Lox code inside them
+                native
+                    .call(&arguments)
+                    .map_err(|kind| LoxError::runtime(a1, kind))
+            }
+            _ => unreachable!(),
+        };
+        self.depth -= 1;
+        Ok(result?)
+    }
+    fn visit_get_expr(&mut self, a0: &Expr, a1: &Token) -> Result<Value, Unwind> {
+        match self.evaluate(a0)? {
+            Value::Instance(instance) => Ok(LoxInstance::get(&instance, a1)?),
+            _ => Err(type_mismatch(a1, "Only instances have properties.").into()),
+        }
+    }
+    fn visit_grouping_expr(&mut self, a0: &Expr) -> Result<Value, Unwind> {
         self.evaluate(a0)
     }
-    fn visit_literal_expr(&mut self, a0: &Value) -> Result<Value, (Token, String)> {
-        Ok(a0.clone())
+    fn visit_index_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> Result<Value, Unwind> {
+        let object = self.evaluate(a0)?;
+        let index = self.evaluate(a2)?;
+        object
+            .get_index(&index)
+            .map_err(|kind| index_error(a1, (a0, &object), (a2, &index), kind).into())
+    }
+    fn visit_interpolation_expr(&mut self, _a0: &Token, a1: &[Expr]) -> Result<Value, Unwind> {
+        let mut text = String::new();
+        for part in a1 {
+            text.push_str(&self.evaluate(part)?.to_string());
+        }
+        Ok(Value::String(Rc::from(text)))
+    }
+    fn visit_list_expr(&mut self, _a0: &Token, a1: &[Expr], _a2: &Token) -> Result<Value, Unwind> {
+        let mut elements = Vec::with_capacity(a1.len());
+        for element in a1 {
+            elements.push(self.evaluate(element)?);
+        }
+        Ok(Value::List(Rc::new(RefCell::new(elements))))
     }
-    fn visit_unary_expr(&mut self, a0: &Token, a1: &Expr) -> Result<Value, (Token, String)> {
+    fn visit_literal_expr(&mut self, _a0: &Token, a1: &Value) -> Result<Value, Unwind> {
+        Ok(a1.clone())
+    }
+    fn visit_map_expr(
+        &mut self,
+        a0: &Token,
+        a1: &[Expr],
+        a2: &[Expr],
+        _a3: &Token,
+    ) -> Result<Value, Unwind> {
+        let mut map = LoxMap::new();
+        for (key_expr, value) in a1.iter().zip(a2) {
+            let key = self.evaluate(key_expr)?;
+            let value = self.evaluate(value)?;
+            let key = Key::new(&key).map_err(|kind| {
+                LoxError::runtime(a0, kind)
+                    .with_label(key_expr.span(), format!("this is {}", key.describe()))
+            })?;
+            map.insert(key, value);
+        }
+        Ok(Value::Map(Rc::new(RefCell::new(map))))
+    }
+    fn visit_logical_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> Result<Value, Unwind> {
+        let left = self.evaluate(a0)?;
+
+        // short-circuit, handing back the operand itself rather than a bool
+        let short_circuits = match a1.kind {
+            TokenType::OR => is_truthy(left.clone()),
+            TokenType::AND => !is_truthy(left.clone()),
+            _ => unreachable!(),
+        };
+        if short_circuits {
+            Ok(left)
+        } else {
+            self.evaluate(a2)
+        }
+    }
+    fn visit_set_expr(&mut self, a0: &Expr, a1: &Token, a2: &Expr) -> Result<Value, Unwind> {
+        let instance = match self.evaluate(a0)? {
+            Value::Instance(instance) => instance,
+            _ => return Err(type_mismatch(a1, "Only instances have fields.").into()),
+        };
+
+        let value = self.evaluate(a2)?;
+        instance.borrow_mut().set(a1, value.clone());
+        Ok(value)
+    }
+    fn visit_setindex_expr(
+        &mut self,
+        a0: &Expr,
+        a1: &Token,
+        a2: &Expr,
+        a3: &Expr,
+    ) -> Result<Value, Unwind> {
+        let object = self.evaluate(a0)?;
+        let index = self.evaluate(a2)?;
+        let value = self.evaluate(a3)?;
+        object
+            .set_index(&index, value.clone())
+            .map_err(|kind| index_error(a1, (a0, &object), (a2, &index), kind))?;
+        Ok(value)
+    }
+    fn visit_super_expr(
+        &mut self,
+        a0: &Token,
+        a1: &Token,
+        a2: &Cell<Option<usize>>,
+    ) -> Result<Value, Unwind> {
+        let distance = a2.get().expect("'super' is always resolved as a local");
+        let superclass = match self.environment.borrow().get_at(distance, a0)? {
+            Value::Class(class) => class,
+            _ => unreachable!("'super' is always bound to a class"),
+        };
+        // `this` is always bound one scope inside `super`
+        let object = match self
+            .environment
+            .borrow()
+            .lookup_at(distance - 1, Symbol::THIS)
+        {
+            Some(Value::Instance(instance)) => instance,
+            _ => unreachable!("'super' is only bound inside methods"),
+        };
+
+        match superclass.find_method(a1.name()) {
+            Some(method) => Ok(Value::Callable(Rc::new(method.bind(object)))),
+            None => Err(LoxError::runtime(
+                a1,
+                RuntimeErrorKind::UndefinedProperty(a1.lexeme.to_string()),
+            )
+            .into()),
+        }
+    }
+    fn visit_this_expr(&mut self, a0: &Token, a1: &Cell<Option<usize>>) -> Result<Value, Unwind> {
+        Ok(self.look_up_variable(a0, a1)?)
+    }
+    fn visit_unary_expr(&mut self, a0: &Token, a1: &Expr) -> Result<Value, Unwind> {
         let right = self.evaluate(a1)?;
         match a0.kind {
             TokenType::MINUS => {
-                check_number_operand(a0, &right)?;
+                check_number_operand(a0, (a1, &right))?;
                 Ok(Value::Number(-right.unwrap_number()))
             }
             TokenType::BANG => Ok(Value::Bool(!is_truthy(right))),
             _ => unreachable!(),
         }
     }
-    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr) -> Result<Value, (Token, String)> {
+    fn visit_assign_expr(
+        &mut self,
+        a0: &Token,
+        a1: &Expr,
+        a2: &Cell<Option<usize>>,
+    ) -> Result<Value, Unwind> {
         let value = self.evaluate(a1)?;
 
-        self.environment.assign(a0, &value)?;
+        match a2.get() {
+            Some(distance) => self
+                .environment
+                .borrow_mut()
+                .assign_at(distance, a0, &value),
+            None => self.globals.borrow_mut().assign(a0, &value)?,
+        }
         Ok(value)
     }
-    fn visit_variable_expr(&mut self, a0: &Token) -> Result<Value, (Token, String)> {
-        self.environment.get(a0)
+    fn visit_variable_expr(
+        &mut self,
+        a0: &Token,
+        a1: &Cell<Option<usize>>,
+    ) -> Result<Value, Unwind> {
+        Ok(self.look_up_variable(a0, a1)?)
     }
 }
 
-impl<'i> stmt::Visitor<Result<Value, (Token, String)>> for Interpreter<'i> {
-    fn visit_expression_stmt(&mut self, a0: &Expr) -> Result<Value, (Token, String)> {
+impl stmt::Visitor<Result<Value, Unwind>> for Interpreter {
+    fn visit_block_stmt(&mut self, a0: &[Stmt]) -> Result<Value, Unwind> {
+        let enclosing = Rc::clone(&self.environment);
+        self.execute_block(a0, Environment::new(Some(enclosing)))
+    }
+    fn visit_class_stmt(
+        &mut self,
+        _a0: &Token,
+        a1: &Token,
+        a2: &Option<Expr>,
+        a3: &[Stmt],
+    ) -> Result<Value, Unwind> {
+        let superclass = match a2 {
+            Some(expr) => match self.evaluate(expr)? {
+                Value::Class(class) => Some(class),
+                _ => {
+                    let token = match expr {
+                        Expr::Variable(token, _) => token.clone(),
+                        _ => a1.clone(),
+                    };
+                    return Err(type_mismatch(&token, "Superclass must be a class.").into());
+                }
+            },
+            None => None,
+        };
+
+        self.environment.borrow_mut().define(a1.name(), Value::Nil);
+
+        // methods of a subclass close over an extra scope that holds `super`
+        let enclosing = Rc::clone(&self.environment);
+        if let Some(ref superclass) = superclass {
+            let mut environment = Environment::new(Some(Rc::clone(&enclosing)));
+            environment.define(Symbol::SUPER, Value::Class(Rc::clone(superclass)));
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+
+        let mut methods = HashMap::new();
+        for method in a3 {
+            if let Stmt::Function(_, name, params, body) = method {
+                let function = LoxFunction::new(
+                    name.clone(),
+                    params.clone(),
+                    Rc::clone(body),
+                    Rc::clone(&self.environment),
+                    name.name() == Symbol::INIT,
+                );
+                methods.insert(name.name(), Rc::new(function));
+            }
+        }
+
+        let class = LoxClass::new(a1.name(), superclass, methods);
+        self.environment = enclosing;
+        self.environment
+            .borrow_mut()
+            .assign(a1, &Value::Class(Rc::new(class)))?;
+        Ok(Value::Nil)
+    }
+    fn visit_expression_stmt(&mut self, a0: &Expr) -> Result<Value, Unwind> {
         self.evaluate(a0)
     }
-    fn visit_print_stmt(&mut self, a0: &Expr) -> Result<Value, (Token, String)> {
-        let value = self.evaluate(a0)?;
-        println!("{}", value);
+    fn visit_function_stmt(
+        &mut self,
+        _a0: &Option<Token>,
+        a1: &Token,
+        a2: &[Token],
+        a3: &Rc<Vec<Stmt>>,
+    ) -> Result<Value, Unwind> {
+        let function = LoxFunction::new(
+            a1.clone(),
+            a2.to_vec(),
+            Rc::clone(a3),
+            Rc::clone(&self.environment),
+            false,
+        );
+        self.environment
+            .borrow_mut()
+            .define(a1.name(), Value::Callable(Rc::new(function)));
+        Ok(Value::Nil)
+    }
+    fn visit_if_stmt(
+        &mut self,
+        _a0: &Token,
+        a1: &Expr,
+        a2: &Stmt,
+        a3: &Option<Box<Stmt>>,
+    ) -> Result<Value, Unwind> {
+        if is_truthy(self.evaluate(a1)?) {
+            self.execute(a2)?;
+        } else if let Some(else_branch) = a3 {
+            self.execute(else_branch)?;
+        }
+        Ok(Value::Nil)
+    }
+    fn visit_print_stmt(&mut self, _a0: &Token, a1: &Expr) -> Result<Value, Unwind> {
+        let value = self.evaluate(a1)?;
+        writeln!(self.output.borrow_mut(), "{}", value).map_err(LoxError::from)?;
         Ok(Value::Nil)
     }
-    fn visit_var_stmt(&mut self, a0: &Token, a1: &Option<Expr>) -> Result<Value, (Token, String)> {
+    fn visit_return_stmt(&mut self, _a0: &Token, a1: &Option<Expr>) -> Result<Value, Unwind> {
+        let value = match a1 {
+            Some(value) => self.evaluate(value)?,
+            None => Value::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+    fn visit_var_stmt(
+        &mut self,
+        _a0: &Token,
+        a1: &Token,
+        a2: &Option<Expr>,
+    ) -> Result<Value, Unwind> {
         let mut value = Value::Nil;
-        if let Some(initializer) = a1 {
+        if let Some(initializer) = a2 {
             value = self.evaluate(initializer)?;
         }
 
-        self.environment.define(&a0.lexeme, value);
+        self.environment.borrow_mut().define(a1.name(), value);
         // returns nil here because assignment is a statement with no value
         Ok(Value::Nil)
     }
+    fn visit_while_stmt(&mut self, _a0: &Token, a1: &Expr, a2: &Stmt) -> Result<Value, Unwind> {
+        while is_truthy(self.evaluate(a1)?) {
+            self.execute(a2)?;
+        }
+        Ok(Value::Nil)
+    }
 }
 
-fn is_truthy(value: Value) -> bool {
+pub(crate) fn is_truthy(value: Value) -> bool {
     match value {
         Value::Nil => false,
         Value::Bool(b) => b,
@@ -129,64 +457,166 @@ fn is_truthy(value: Value) -> bool {
     }
 }
 
-fn is_equal(v: Value, w: Value) -> bool {
-    if v.is_nil() && w.is_nil() {
-        true
-    } else if v.is_nil() {
-        false
-    } else if v.is_string() && w.is_string() {
-        v.unwrap_string() == w.unwrap_string()
-    } else if v.is_number() && w.is_number() {
-        v.unwrap_number() - w.unwrap_number() < f64::EPSILON
-    } else if v.is_bool() && w.is_bool() {
-        v.unwrap_bool() == w.unwrap_bool()
-    } else {
-        false
+pub(crate) fn is_equal(v: Value, w: Value) -> bool {
+    match (&v, &w) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::String(a), Value::String(b)) => a == b,
+        // plain IEEE equality, so NaN isn't even equal to itself
+        (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        // functions, classes and instances are only ever equal to themselves
+        (Value::Callable(f), Value::Callable(g)) => Rc::ptr_eq(f, g),
+        (Value::Class(c), Value::Class(d)) => Rc::ptr_eq(c, d),
+        (Value::Instance(i), Value::Instance(j)) => Rc::ptr_eq(i, j),
+        (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b),
+        (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b),
+        (Value::Native(f), Value::Native(g)) => Rc::ptr_eq(f, g),
+        (Value::Object(a), Value::Object(b)) => a == b,
+        _ => false,
     }
 }
 
-fn check_number_operand(operator: &Token, operand: &Value) -> Result<(), (Token, String)> {
-    if operand.is_number() {
+/// Operands come along with the expressions they came from, so the error can
+/// point at whichever one isn't a number.
+fn check_number_operand(operator: &Token, operand: (&Expr, &Value)) -> Result<(), LoxError> {
+    let (expr, value) = operand;
+    if value.is_number() {
         Ok(())
     } else {
-        Err((operator.clone(), "Operand must be a number.".to_string()))
+        Err(type_mismatch(operator, "Operand must be a number.")
+            .with_label(expr.span(), format!("this is {}", value.describe())))
     }
 }
 
-fn check_number_operands(operator: &Token, v: &Value, w: &Value) -> Result<(), (Token, String)> {
-    if v.is_number() && w.is_number() {
-        Ok(())
-    } else {
-        Err((operator.clone(), "Operand must be a number.".to_string()))
+fn check_number_operands(
+    operator: &Token,
+    left: (&Expr, &Value),
+    right: (&Expr, &Value),
+) -> Result<(), LoxError> {
+    if left.1.is_number() && right.1.is_number() {
+        return Ok(());
+    }
+
+    let mut error = type_mismatch(operator, "Operands must be numbers.");
+    for (expr, value) in [left, right].iter() {
+        if !value.is_number() {
+            error = error.with_label(expr.span(), format!("this is {}", value.describe()));
+        }
+    }
+    Err(error)
+}
+
+/// Errors from indexing point at the bracket, and at whichever operand is the
+/// wrong type, if that's the problem.
+fn index_error(
+    bracket: &Token,
+    object: (&Expr, &Value),
+    index: (&Expr, &Value),
+    kind: RuntimeErrorKind,
+) -> LoxError {
+    let culprit = match index_culprit(object.1, &kind) {
+        Some(0) => object,
+        Some(_) => index,
+        None => return LoxError::runtime(bracket, kind),
+    };
+    LoxError::runtime(bracket, kind).with_label(
+        culprit.0.span(),
+        format!("this is {}", culprit.1.describe()),
+    )
+}
+
+/// Which operand of an indexing operation is to blame for an error: the
+/// object (0) if it can't be indexed, the index (1) if it's the wrong type
+/// for it, or neither if the index just isn't there.
+pub(crate) fn index_culprit(object: &Value, kind: &RuntimeErrorKind) -> Option<usize> {
+    match object {
+        Value::List(_) | Value::Map(_) => match kind {
+            RuntimeErrorKind::TypeMismatch(_) => Some(1),
+            _ => None,
+        },
+        _ => Some(0),
     }
 }
 
-impl<'i> Interpreter<'i> {
+fn type_mismatch(token: &Token, message: &'static str) -> LoxError {
+    LoxError::runtime(token, RuntimeErrorKind::TypeMismatch(message))
+}
+
+impl Interpreter {
     pub fn new() -> Self {
+        let mut environment = Environment::new(None);
+        for native in native::builtins() {
+            environment.define(native.name, Value::Native(Rc::new(native)));
+        }
+
+        let globals = Rc::new(RefCell::new(environment));
         Self {
-            environment: Environment::new(None),
+            environment: Rc::clone(&globals),
+            globals,
+            output: Rc::new(RefCell::new(io::stdout())),
+            depth: 0,
         }
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Result<Value, (Token, String)> {
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    fn look_up_variable(
+        &self,
+        name: &Token,
+        depth: &Cell<Option<usize>>,
+    ) -> Result<Value, LoxError> {
+        match depth.get() {
+            Some(distance) => self.environment.borrow().get_at(distance, name),
+            None => self.globals.borrow().get(name),
+        }
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Value, Unwind> {
         expr.accept(self)
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> Result<Value, (Token, String)> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<Value, Unwind> {
         stmt.accept(self)
     }
 
-    pub fn interpret(&mut self, statements: &[Stmt]) {
+    /// Run `statements` inside `environment`, then put the current scope back
+    /// the way it was, even if one of them fails.
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<Value, Unwind> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement).map(|_| ()));
+        self.environment = previous;
+        result.map(|_| Value::Nil)
+    }
+
+    /// Run a whole program. Hands back the value of the last statement, or
+    /// the first runtime error.
+    pub fn interpret(&mut self, statements: &[Stmt]) -> Result<Value, LoxError> {
+        let mut value = Value::Nil;
         for statement in statements {
-            if let Err(e) = self.execute(statement) {
-                crate::runtime_error(e);
-                break;
+            match self.execute(statement) {
+                Ok(v) => value = v,
+                Err(Unwind::Error(e)) => return Err(e),
+                // the resolver doesn't allow top-level returns, but just in case
+                Err(Unwind::Return(_)) => break,
             }
         }
+        Ok(value)
+    }
+
+    pub fn globals(&self) -> &Rc<RefCell<Environment>> {
+        &self.globals
     }
 }
 
-impl<'i> Default for Interpreter<'i> {
+impl Default for Interpreter {
     fn default() -> Self {
         Self::new()
     }
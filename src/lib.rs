@@ -0,0 +1,242 @@
+//! An interpreter for Lox, with two backends: a tree-walker, and a bytecode
+//! compiler with a VM to run it on.
+//!
+//! Most of the time you'll want a [`Lox`] engine, which holds onto its global
+//! state between calls:
+//!
+//! ```
+//! use jlox::{Lox, Value};
+//!
+//! let mut lox = Lox::new();
+//! lox.eval("var greeting = \"hi\";").unwrap();
+//! assert_eq!(lox.global("greeting").unwrap().unwrap_string(), "hi");
+//! ```
+
+pub mod ast;
+pub mod bytecode;
+pub mod callable;
+pub mod class;
+pub mod diagnostic;
+pub mod environment;
+pub mod error;
+pub mod function;
+pub mod interpreter;
+pub mod literal;
+pub mod map;
+pub mod native;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod symbol;
+pub mod token;
+pub mod token_type;
+
+pub use error::LoxError;
+pub use literal::Value;
+
+use ast::stmt::Stmt;
+use bytecode::{compiler::Compiler, object::Function, vm::Vm};
+use interpreter::Interpreter;
+use native::{NativeFn, NativeFunction};
+use parser::Parser;
+use resolver::Resolver;
+use scanner::Scanner;
+use std::{cell::RefCell, fs, io::Write, path::Path, rc::Rc};
+use symbol::Symbol;
+
+/// Which engine actually runs the code. They should behave the same way.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Backend {
+    /// Walk the syntax tree directly.
+    #[default]
+    TreeWalk,
+    /// Compile to bytecode and run it on a stack VM.
+    Bytecode,
+}
+
+/// How much stack a thread running a [`Lox`] engine should get, so the
+/// tree-walker can reach [`MAX_CALL_DEPTH`](interpreter::MAX_CALL_DEPTH) and
+/// report a stack overflow instead of crashing.
+///
+/// A Lox call whose body nests a few statements deep takes up to about
+/// 30 KiB of Rust stack in a debug build, and about 7 KiB in a release build,
+/// so this leaves room for 64 KiB a call. Only the stack that actually gets
+/// used is touched, so the rest costs address space rather than memory.
+pub const STACK_SIZE: usize = (interpreter::MAX_CALL_DEPTH + 1) * 64 * 1024;
+
+/// Where `print` statements write to. It's shared, so whoever set it can
+/// read back what got printed.
+pub type Output = Rc<RefCell<dyn Write>>;
+
+/// An embeddable Lox engine. Globals defined by one `eval` stick around for the next.
+///
+/// The tree-walker uses some Rust stack for every Lox call, so deeply
+/// recursive programs need more than the few megabytes a thread usually gets
+/// before they hit the call depth limit and stop with a stack overflow error.
+/// Run the engine on a thread with a [`STACK_SIZE`] stack if that might happen.
+pub struct Lox {
+    backend: Backend,
+    interpreter: Interpreter,
+    vm: Vm,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Self::with_backend(Backend::default())
+    }
+
+    pub fn with_backend(backend: Backend) -> Self {
+        Self {
+            backend,
+            interpreter: Interpreter::new(),
+            vm: Vm::new(),
+        }
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Send everything Lox code prints to `output`, instead of stdout.
+    pub fn set_output(&mut self, output: Output) {
+        self.interpreter.set_output(Rc::clone(&output));
+        self.vm.set_output(output);
+    }
+
+    /// Run some Lox source code. Hands back the value of the last statement,
+    /// if it was an expression statement, or `nil` otherwise.
+    pub fn eval<S>(&mut self, source: S) -> Result<Value, LoxError>
+    where
+        S: AsRef<str>,
+    {
+        self.eval_at(source, 0)
+    }
+
+    /// Like `eval`, but for source code that starts `offset` bytes into some
+    /// bigger text, like everything typed into a REPL so far. Spans in errors
+    /// point into that text, so they can still be shown against the right
+    /// code when it was defined by an earlier `eval`.
+    pub fn eval_at<S>(&mut self, source: S, offset: usize) -> Result<Value, LoxError>
+    where
+        S: AsRef<str>,
+    {
+        let statements = Self::check(source, offset)?;
+        match self.backend {
+            Backend::TreeWalk => self.interpreter.interpret(&statements),
+            Backend::Bytecode => {
+                let script = Compiler::new()
+                    .compile(&statements)
+                    .map_err(LoxError::Static)?;
+                self.vm.interpret(script)
+            }
+        }
+    }
+
+    /// Compile some Lox source code to bytecode without running it, say to
+    /// save it with `bytecode::loxc`.
+    pub fn compile<S>(&self, source: S) -> Result<Rc<Function>, LoxError>
+    where
+        S: AsRef<str>,
+    {
+        let statements = Self::check(source, 0)?;
+        Compiler::new()
+            .compile(&statements)
+            .map_err(LoxError::Static)
+    }
+
+    /// Run a program that was compiled ahead of time. This always uses the
+    /// bytecode VM, whichever backend this engine was made with.
+    pub fn run_compiled(&mut self, script: Rc<Function>) -> Result<Value, LoxError> {
+        self.vm.interpret(script)
+    }
+
+    /// Scan, parse and resolve, handing back the program if nothing's wrong with it.
+    fn check<S>(source: S, offset: usize) -> Result<Vec<Stmt>, LoxError>
+    where
+        S: AsRef<str>,
+    {
+        let (tokens, mut errors) = Scanner::with_offset(source.as_ref(), offset).scan_tokens();
+
+        // parse even if scanning went wrong, so all the syntax errors show up at once
+        let mut parser = Parser::new(tokens);
+        let (statements, parse_errors) = parser.parse();
+        errors.extend(parse_errors);
+        if !errors.is_empty() {
+            return Err(LoxError::Static(errors));
+        }
+
+        // resolution errors are caught before anything runs, just like syntax errors
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements).map_err(LoxError::Static)?;
+        Ok(statements)
+    }
+
+    /// Read a script from disk and run it.
+    pub fn run_file<P>(&mut self, path: P) -> Result<(), LoxError>
+    where
+        P: AsRef<Path>,
+    {
+        let source = fs::read_to_string(path)?;
+        self.eval(source).map(|_| ())
+    }
+
+    /// Look up a global variable by name, as `eval` sees it. Programs run with
+    /// `run_compiled` on a tree-walking engine keep their globals to the VM.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        let name = Symbol::intern(name);
+        match self.backend {
+            Backend::TreeWalk => self.interpreter.globals().borrow().lookup(name),
+            Backend::Bytecode => self.vm.globals().get(&name).cloned(),
+        }
+    }
+
+    /// Make a function written in Rust available to Lox code, as a global
+    /// called `name`, whichever way the code is run. It gets exactly `arity`
+    /// arguments, and any error it hands back becomes a runtime error at the
+    /// call:
+    ///
+    /// ```
+    /// use jlox::{Lox, Value};
+    ///
+    /// let mut lox = Lox::new();
+    /// lox.register("double", 1, |arguments| match arguments[0] {
+    ///     Value::Number(n) => Ok(Value::Number(n * 2.0)),
+    ///     _ => Err("Can only double numbers.".into()),
+    /// });
+    /// assert_eq!(lox.eval("double(21);").unwrap().unwrap_number(), 42.0);
+    ///
+    /// let error = lox.eval("double(\"two\");").unwrap_err();
+    /// assert_eq!(error.to_string(), "Can only double numbers.\n[line 1]");
+    ///
+    /// let script = lox.compile("double(4);").unwrap();
+    /// assert_eq!(lox.run_compiled(script).unwrap().unwrap_number(), 8.0);
+    /// ```
+    pub fn register<S, F>(&mut self, name: S, arity: usize, function: F)
+    where
+        S: Into<Symbol>,
+        F: NativeFn + 'static,
+    {
+        let native = NativeFunction::new(name, arity, function);
+        self.define_global(native.name, Value::Native(Rc::new(native)));
+    }
+
+    /// Define (or redefine) a global variable, visible to all code run from
+    /// now on, including programs run with `run_compiled`.
+    pub fn define_global<S>(&mut self, name: S, value: Value)
+    where
+        S: Into<Symbol>,
+    {
+        let name = name.into();
+        self.interpreter
+            .globals()
+            .borrow_mut()
+            .define(name, value.clone());
+        self.vm.globals_mut().insert(name, value);
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
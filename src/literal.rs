@@ -1,25 +1,238 @@
-use std::fmt::Display;
+use crate::{
+    bytecode::object::Object,
+    callable::Callable,
+    class::{LoxClass, LoxInstance},
+    error::RuntimeErrorKind,
+    map::LoxMap,
+    native::NativeFunction,
+};
+use std::{cell::RefCell, fmt::Display, rc::Rc};
+
+/// A list's elements. Lists are shared, and changes show through every reference.
+pub type List = Rc<RefCell<Vec<Value>>>;
+
+/// A map's entries. Shared, just like lists.
+pub type Map = Rc<RefCell<LoxMap>>;
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Nil,
-    String(String),
+    /// Shared, since strings are never changed in place.
+    String(Rc<str>),
     Number(f64),
     Bool(bool),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(List),
+    Map(Map),
+    /// A function built into the interpreter, which either backend can call.
+    Native(Rc<NativeFunction>),
+    /// A function, class or instance made by the bytecode VM.
+    Object(Object),
 }
 
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Nil => write!(f, ""),
+            Value::Nil => write!(f, "nil"),
             Value::String(s) => write!(f, "{}", s),
-            Value::Number(n) => write!(f, "{}", n),
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
             Value::Bool(b) => write!(f, "{}", if *b { "true" } else { "false" }),
+            Value::Callable(c) => write!(f, "{}", c),
+            Value::Class(c) => write!(f, "{}", c),
+            Value::Instance(i) => write!(f, "{}", i.borrow()),
+            Value::List(list) => print_once(list, f, "[...]", |f| {
+                write!(f, "[")?;
+                for (i, element) in list.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }),
+            Value::Map(map) => print_once(map, f, "{...}", |f| {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.borrow().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.to_value(), value)?;
+                }
+                write!(f, "}}")
+            }),
+            Value::Native(n) => write!(f, "{}", n),
+            Value::Object(o) => write!(f, "{}", o),
+        }
+    }
+}
+
+thread_local! {
+    /// The lists and maps being printed right now, outermost first.
+    static PRINTING: RefCell<Vec<*const ()>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Print a list or map with `print`, unless it's already being printed
+/// further out. Then it's inside itself, so it shows up as `placeholder`
+/// rather than going round forever.
+fn print_once<T>(
+    container: &Rc<T>,
+    f: &mut std::fmt::Formatter<'_>,
+    placeholder: &str,
+    print: impl FnOnce(&mut std::fmt::Formatter<'_>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    let pointer = Rc::as_ptr(container) as *const ();
+    if PRINTING.with(|printing| printing.borrow().contains(&pointer)) {
+        return write!(f, "{}", placeholder);
+    }
+
+    PRINTING.with(|printing| printing.borrow_mut().push(pointer));
+    let result = print(f);
+    PRINTING.with(|printing| printing.borrow_mut().pop());
+    result
+}
+
+/// A value that can be used as a map key. Only strings, numbers, booleans
+/// and nil can be, since they're compared by value and never change.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Nil,
+    Bool(bool),
+    /// The bits of the number. `-0` is folded into `0`, and every NaN into
+    /// one, so numbers that print the same are the same key.
+    Number(u64),
+    String(Rc<str>),
+}
+
+impl Key {
+    pub fn new(value: &Value) -> Result<Self, RuntimeErrorKind> {
+        match value {
+            Value::Nil => Ok(Key::Nil),
+            Value::Bool(b) => Ok(Key::Bool(*b)),
+            Value::Number(n) if *n == 0.0 => Ok(Key::Number(0f64.to_bits())),
+            Value::Number(n) if n.is_nan() => Ok(Key::Number(f64::NAN.to_bits())),
+            Value::Number(n) => Ok(Key::Number(n.to_bits())),
+            Value::String(s) => Ok(Key::String(Rc::clone(s))),
+            _ => Err(RuntimeErrorKind::TypeMismatch(
+                "Map keys must be strings, numbers, booleans or nil.",
+            )),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Key::Nil => Value::Nil,
+            Key::Bool(b) => Value::Bool(*b),
+            Key::Number(bits) => Value::Number(f64::from_bits(*bits)),
+            Key::String(s) => Value::String(Rc::clone(s)),
         }
     }
 }
 
+/// Which element of a list of `length` elements `index` refers to. It has to
+/// be a whole number, and in bounds.
+fn list_index(index: &Value, length: usize) -> Result<usize, RuntimeErrorKind> {
+    let index = match index {
+        Value::Number(n) => *n,
+        _ => {
+            return Err(RuntimeErrorKind::TypeMismatch(
+                "List index must be a number.",
+            ))
+        }
+    };
+    if index.fract() != 0.0 {
+        return Err(RuntimeErrorKind::TypeMismatch(
+            "List index must be a whole number.",
+        ));
+    }
+    if index < 0.0 || index >= length as f64 {
+        return Err(RuntimeErrorKind::IndexOutOfBounds { index, length });
+    }
+    Ok(index as usize)
+}
+
+/// Format a number the way the reference jlox does, which is Java's
+/// `Double.toString` with any trailing `.0` taken off: `3`, `2.5`, `1.0E10`,
+/// `1.5E-4`, `NaN`, `-Infinity`.
+pub fn format_number(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+
+    // Java only switches to scientific notation outside of [10^-3, 10^7)
+    let magnitude = n.abs();
+    if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        // both use the shortest digits that read back as the same number, so
+        // this only differs by Java's trailing `.0`, which jlox strips anyway
+        return n.to_string();
+    }
+
+    // Java always has a fraction in the mantissa, even if it's just `.0`
+    let scientific = format!("{:e}", n);
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let point = if mantissa.contains('.') { "" } else { ".0" };
+    format!("{}{}E{}", mantissa, point, &exponent[1..])
+}
+
 impl Value {
+    /// What sort of value this is, for error messages. Reads well after "this is".
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Value::Nil => "nil",
+            Value::String(_) => "a string",
+            Value::Number(_) => "a number",
+            Value::Bool(_) => "a boolean",
+            Value::Callable(_) => "a function",
+            Value::Class(_) => "a class",
+            Value::Instance(_) => "an instance",
+            Value::List(_) => "a list",
+            Value::Map(_) => "a map",
+            Value::Native(_) => "a function",
+            Value::Object(o) => o.describe(),
+        }
+    }
+
+    /// Look up `self[index]`, for a list or a map.
+    pub fn get_index(&self, index: &Value) -> Result<Value, RuntimeErrorKind> {
+        match self {
+            Value::List(list) => {
+                let list = list.borrow();
+                Ok(list[list_index(index, list.len())?].clone())
+            }
+            Value::Map(map) => match map.borrow().get(&Key::new(index)?) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeErrorKind::UndefinedKey(index.to_string())),
+            },
+            _ => Err(RuntimeErrorKind::TypeMismatch(
+                "Only lists and maps can be indexed.",
+            )),
+        }
+    }
+
+    /// Do `self[index] = value`, for a list or a map. Lists have to have an
+    /// element there already, but maps get a new entry if need be.
+    pub fn set_index(&self, index: &Value, value: Value) -> Result<(), RuntimeErrorKind> {
+        match self {
+            Value::List(list) => {
+                let mut list = list.borrow_mut();
+                let index = list_index(index, list.len())?;
+                list[index] = value;
+                Ok(())
+            }
+            Value::Map(map) => {
+                map.borrow_mut().insert(Key::new(index)?, value);
+                Ok(())
+            }
+            _ => Err(RuntimeErrorKind::TypeMismatch(
+                "Only lists and maps can be indexed.",
+            )),
+        }
+    }
+
     pub fn unwrap_nil(&self) {
         match self {
             Value::Nil => {}
@@ -49,30 +262,18 @@ impl Value {
     }
 
     pub fn is_nil(&self) -> bool {
-        match self {
-            Value::Nil => true,
-            _ => false,
-        }
+        matches!(self, Value::Nil)
     }
 
     pub fn is_string(&self) -> bool {
-        match self {
-            Value::String(_) => true,
-            _ => false,
-        }
+        matches!(self, Value::String(_))
     }
 
     pub fn is_number(&self) -> bool {
-        match self {
-            Value::Number(_) => true,
-            _ => false,
-        }
+        matches!(self, Value::Number(_))
     }
 
     pub fn is_bool(&self) -> bool {
-        match self {
-            Value::Bool(_) => true,
-            _ => false,
-        }
+        matches!(self, Value::Bool(_))
     }
 }
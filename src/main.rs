@@ -1,62 +1,140 @@
-pub mod ast;
-pub mod environment;
-pub mod interpreter;
-pub mod literal;
-pub mod parser;
-pub mod scanner;
-pub mod token;
-pub mod token_type;
-
-use exit::Exit;
-use interpreter::Interpreter;
-use io::{BufRead, BufReader, Read, Write};
-use lazy_static::lazy_static;
-use parking_lot::{Mutex, RwLock};
-use parser::Parser;
-use scanner::Scanner;
-use std::{env, error::Error, fs::File, io};
-use token::Token;
-
-lazy_static! {
-    static ref HAD_ERROR: RwLock<bool> = RwLock::new(false);
-    static ref HAD_RUNTIME_ERROR: RwLock<bool> = RwLock::new(false);
-    static ref INTERPRETER: Mutex<Interpreter<'static>> = Mutex::new(Interpreter::new());
+use io::{BufRead, BufReader, Write};
+use jlox::{
+    bytecode::{disassembler, loxc, object::Function},
+    diagnostic::Style,
+    Backend, Lox, LoxError, STACK_SIZE,
+};
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, IsTerminal},
+    path::Path,
+    process::ExitCode,
+    rc::Rc,
+    thread,
+};
+
+const USAGE: &str = "Usage: jlox [--vm] [script]
+       jlox compile <script> [output]
+       jlox disassemble <script>";
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect();
+    let interpreter = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(|| run(args))
+        .expect("couldn't start the interpreter thread");
+    match interpreter.join() {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(code)) => ExitCode::from(code),
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
 }
 
-fn main() -> Exit<i8> {
-    let args: Vec<_> = env::args().skip(1).collect();
+/// Do whatever the arguments ask for, handing back the exit code if it didn't work out.
+fn run(mut args: Vec<String>) -> Result<(), u8> {
+    let mut backend = Backend::TreeWalk;
+    if args.first().map(String::as_str) == Some("--vm") {
+        backend = Backend::Bytecode;
+        args.remove(0);
+    }
 
-    match args.len() {
-        0 => run_prompt().map(|_| Exit::Ok).map_err(|_| 1)?,
-        1 => run_file(&args[0]).map_err(|_| 1).map(|_| Exit::Ok)?,
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => run_prompt(backend).map_err(|_| 1),
+        ["compile", script] => compile(script, &Path::new(script).with_extension("loxc")),
+        ["compile", script, output] => compile(script, Path::new(output)),
+        ["disassemble", script] => disassemble(script),
+        [script] if !script.starts_with("--") => run_file(script, backend),
         _ => {
-            eprintln!("Usage: jlox [script]");
-            Exit::Err(64)
+            eprintln!("{}", USAGE);
+            Err(64)
         }
     }
 }
 
-fn run_file(path: &str) -> Result<(), i8> {
-    let mut bytes = vec![];
-    (|| -> Result<(), Box<dyn Error>> {
-        File::open(path)?.read_to_end(&mut bytes)?;
-        run(String::from_utf8(bytes)?)?;
-        Ok(())
-    })()
-    .map_err(|_| 1)?;
+/// Scripts ending in `.loxc` have already been compiled.
+fn is_compiled(path: &str) -> bool {
+    Path::new(path).extension().and_then(|ext| ext.to_str()) == Some("loxc")
+}
 
-    if *HAD_ERROR.read() {
-        return Err(65);
-    }
-    if *HAD_RUNTIME_ERROR.read() {
-        return Err(70);
-    }
+fn read_source(path: &str) -> Result<String, u8> {
+    fs::read_to_string(path).map_err(|error| {
+        eprintln!("{}: {}", path, error);
+        1
+    })
+}
+
+fn load_compiled(path: &str) -> Result<Rc<Function>, u8> {
+    fs::read(path)
+        .and_then(|bytes| loxc::deserialize(&bytes))
+        .map_err(|error| {
+            eprintln!("{}: {}", path, error);
+            1
+        })
+}
+
+fn compile(path: &str, output: &Path) -> Result<(), u8> {
+    let source = read_source(path)?;
+    let script = Lox::new().compile(&source).map_err(|error| {
+        report(&error, path, &source);
+        error.exit_code()
+    })?;
+
+    loxc::serialize(&script)
+        .and_then(|bytes| fs::write(output, bytes))
+        .map_err(|error| {
+            eprintln!("{}: {}", output.display(), error);
+            1
+        })
+}
+
+fn disassemble(path: &str) -> Result<(), u8> {
+    let script = if is_compiled(path) {
+        load_compiled(path)?
+    } else {
+        let source = read_source(path)?;
+        Lox::new().compile(&source).map_err(|error| {
+            report(&error, path, &source);
+            error.exit_code()
+        })?
+    };
+    print!("{}", disassembler::disassemble(&script));
     Ok(())
 }
 
-fn run_prompt() -> Result<(), Box<dyn Error>> {
+fn run_file(path: &str, backend: Backend) -> Result<(), u8> {
+    if is_compiled(path) {
+        let script = load_compiled(path)?;
+        // there's no source code to show, so stick to the plain message
+        return Lox::new()
+            .run_compiled(script)
+            .map(|_| ())
+            .map_err(|error| {
+                eprintln!("{}", error);
+                error.exit_code()
+            });
+    }
+
+    // read it in here rather than with `Lox::run_file`, errors need the source to show it
+    let source = read_source(path)?;
+    Lox::with_backend(backend)
+        .eval(&source)
+        .map(|_| ())
+        .map_err(|error| {
+            report(&error, path, &source);
+            error.exit_code()
+        })
+}
+
+fn run_prompt(backend: Backend) -> Result<(), Box<dyn Error>> {
     let stdin = io::stdin();
     let mut reader = BufReader::new(stdin.lock());
+    // one engine for the whole session, so variables stick around between lines
+    let mut lox = Lox::with_backend(backend);
+    // everything typed so far. a runtime error can be in a function from an
+    // earlier line, so errors get shown against all of it
+    let mut session = String::new();
     loop {
         print!("> ");
         io::stdout().flush()?;
@@ -65,64 +143,24 @@ fn run_prompt() -> Result<(), Box<dyn Error>> {
             break;
         }
 
-        run(line)?;
-
-        *HAD_ERROR.write() = false;
-    }
-    Ok(())
-}
-
-fn run<S>(source: S) -> Result<(), Box<dyn Error>>
-where
-    S: AsRef<str>,
-{
-    let source = source.as_ref();
-
-    let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
-
-    let mut parser = Parser::new(tokens);
-    let statements = parser.parse();
-    if *HAD_ERROR.read() {
-        return Ok(());
+        let offset = session.len();
+        session.push_str(&line);
+        // a mistake on one line shouldn't end the session
+        if let Err(error) = lox.eval_at(&line, offset) {
+            report(&error, "<stdin>", &session);
+        }
     }
-
-    INTERPRETER.lock().interpret(&statements.unwrap());
     Ok(())
 }
 
-fn error<S>(line: usize, message: S)
-where
-    S: AsRef<str>,
-{
-    report(line, "", message.as_ref())
-}
-
-fn error_token<S>(token: Token, message: S)
-where
-    S: AsRef<str>,
-{
-    match token.kind {
-        token_type::TokenType::EOF => report(token.line, " at end", message),
-        _ => report(token.line, format!(" at '{}'", token.lexeme), message),
+/// Show every problem in `error` on stderr, with the bits of `source` they're about.
+fn report(error: &LoxError, name: &str, source: &str) {
+    let style = if io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() {
+        Style::Colored
+    } else {
+        Style::Plain
+    };
+    for diagnostic in error.diagnostics() {
+        eprint!("{}", diagnostic.render(name, source, style));
     }
 }
-
-fn runtime_error(error: (Token, String)) {
-    println!("{}\n[line {}]", error.1, error.0.line);
-    *HAD_RUNTIME_ERROR.write() = true;
-}
-
-fn report<S, S2>(line: usize, whence: S, message: S2)
-where
-    S: AsRef<str>,
-    S2: AsRef<str>,
-{
-    eprintln!(
-        "[line {}] Error{}: {}",
-        line,
-        whence.as_ref(),
-        message.as_ref()
-    );
-    *HAD_ERROR.write() = true;
-}
@@ -0,0 +1,65 @@
+use crate::literal::{Key, Value};
+use std::collections::HashMap;
+
+/// The entries of a Lox map. It remembers the order keys went in, so printing
+/// a map or going through its keys always happens in the same order.
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(Key, Value)>,
+    /// Where each key's entry is in `entries`.
+    indices: HashMap<Key, usize>,
+}
+
+impl LoxMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&Value> {
+        self.indices.get(key).map(|&index| &self.entries[index].1)
+    }
+
+    pub fn contains_key(&self, key: &Key) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Add an entry, or change the value of an existing one. Changing one
+    /// leaves it where it was in the order.
+    pub fn insert(&mut self, key: Key, value: Value) {
+        match self.indices.get(&key) {
+            Some(&index) => self.entries[index].1 = value,
+            None => {
+                self.indices.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    /// Take an entry out, handing back its value. The entries after it shuffle
+    /// down, so the order of the rest stays the same.
+    pub fn remove(&mut self, key: &Key) -> Option<Value> {
+        let index = self.indices.remove(key)?;
+        let (_, value) = self.entries.remove(index);
+        for (key, _) in &self.entries[index..] {
+            *self.indices.get_mut(key).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    /// The entries, in the order their keys went in.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Key> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+}
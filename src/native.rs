@@ -0,0 +1,147 @@
+use crate::{
+    error::RuntimeErrorKind,
+    literal::{Key, Map, Value},
+    symbol::Symbol,
+};
+use std::{
+    cell::RefCell,
+    fmt::{Debug, Display},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// What a native function does. It gets exactly as many arguments as its
+/// arity says, and any error it hands back is reported as a runtime error at
+/// the call. Messages convert straight into errors, so `Err("Oops.".into())`
+/// works.
+pub trait NativeFn: Fn(&[Value]) -> Result<Value, RuntimeErrorKind> {}
+
+impl<F> NativeFn for F where F: Fn(&[Value]) -> Result<Value, RuntimeErrorKind> {}
+
+/// A function provided by Rust code rather than written in Lox. Both backends
+/// call these directly, so they can't call back into Lox code.
+pub struct NativeFunction {
+    pub name: Symbol,
+    pub arity: usize,
+    function: Box<dyn NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new<S, F>(name: S, arity: usize, function: F) -> Self
+    where
+        S: Into<Symbol>,
+        F: NativeFn + 'static,
+    {
+        Self {
+            name: name.into(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+
+    /// Run the function. `arguments` should already have been checked against `arity`.
+    pub fn call(&self, arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+        (self.function)(arguments)
+    }
+}
+
+impl Debug for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+/// The natives every program starts out with as globals.
+pub fn builtins() -> Vec<NativeFunction> {
+    vec![
+        NativeFunction::new("clock", 0, clock),
+        NativeFunction::new("len", 1, len),
+        NativeFunction::new("push", 2, push),
+        NativeFunction::new("pop", 1, pop),
+        NativeFunction::new("keys", 1, keys),
+        NativeFunction::new("has", 2, has),
+        NativeFunction::new("remove", 2, remove),
+    ]
+}
+
+/// Seconds since the Unix epoch, like the reference jlox's `clock()`. It's
+/// mostly useful for timing things.
+fn clock(_arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Number(now.as_secs_f64()))
+}
+
+/// How many elements a list has, entries a map has, or characters a string has.
+fn len(arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let length = match &arguments[0] {
+        Value::List(list) => list.borrow().len(),
+        Value::Map(map) => map.borrow().len(),
+        Value::String(s) => s.chars().count(),
+        _ => {
+            return Err(RuntimeErrorKind::TypeMismatch(
+                "Can only take the length of a list, map or string.",
+            ))
+        }
+    };
+    Ok(Value::Number(length as f64))
+}
+
+/// Add an element to the end of a list.
+fn push(arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    match &arguments[0] {
+        Value::List(list) => {
+            list.borrow_mut().push(arguments[1].clone());
+            Ok(Value::Nil)
+        }
+        _ => Err(RuntimeErrorKind::TypeMismatch("Can only push onto a list.")),
+    }
+}
+
+/// Take the last element off a list, handing it back.
+fn pop(arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    match &arguments[0] {
+        Value::List(list) => list.borrow_mut().pop().ok_or(RuntimeErrorKind::EmptyList),
+        _ => Err(RuntimeErrorKind::TypeMismatch("Can only pop from a list.")),
+    }
+}
+
+fn expect_map(value: &Value) -> Result<&Map, RuntimeErrorKind> {
+    match value {
+        Value::Map(map) => Ok(map),
+        _ => Err(RuntimeErrorKind::TypeMismatch("Expected a map.")),
+    }
+}
+
+/// A new list of a map's keys, in the order they went in.
+fn keys(arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let map = expect_map(&arguments[0])?;
+    let keys = map.borrow().keys().map(Key::to_value).collect();
+    Ok(Value::List(Rc::new(RefCell::new(keys))))
+}
+
+/// Whether a map has an entry for a key.
+fn has(arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let map = expect_map(&arguments[0])?;
+    let key = Key::new(&arguments[1])?;
+    let has = map.borrow().contains_key(&key);
+    Ok(Value::Bool(has))
+}
+
+/// Take an entry out of a map, handing back its value.
+fn remove(arguments: &[Value]) -> Result<Value, RuntimeErrorKind> {
+    let map = expect_map(&arguments[0])?;
+    let key = Key::new(&arguments[1])?;
+    let removed = map.borrow_mut().remove(&key);
+    removed.ok_or_else(|| RuntimeErrorKind::UndefinedKey(arguments[1].to_string()))
+}
@@ -1,45 +1,142 @@
 use crate::{
     ast::{expr::Expr, stmt::Stmt},
+    diagnostic::suggestion,
+    error::LoxError,
     literal::Value,
     token::Token,
     token_type::TokenType,
 };
+use std::{cell::Cell, rc::Rc};
+
+/// The most arguments a call (or parameters a function) can have.
+const MAX_ARGUMENTS: usize = 255;
+
+/// The most elements a list literal (or entries a map literal) can have.
+const MAX_ELEMENTS: usize = u16::MAX as usize;
+
+/// The most pieces an interpolated string can be made of, counting both the
+/// text and the expressions.
+const MAX_PIECES: usize = u16::MAX as usize;
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    /// Everything that went wrong so far, along with where it went wrong.
+    errors: Vec<LoxError>,
+    /// Where the first statement of the innermost block opened so far starts.
+    /// A map literal there has been taken for a block.
+    block_start: Option<usize>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            errors: vec![],
+            block_start: None,
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ()> {
+    /// Parse the whole program, recovering from mistakes as it goes. Hands back
+    /// every statement that parsed cleanly, along with every error found; if
+    /// there are any errors, the statements are only good for poking at.
+    pub fn parse(&mut self) -> (Vec<Stmt>, Vec<LoxError>) {
         let mut statements: Vec<Stmt> = vec![];
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
-        Ok(statements)
+
+        (statements, std::mem::take(&mut self.errors))
     }
 
-    fn declaration(&mut self) -> Result<Stmt, ()> {
+    /// Parse one declaration. If it's broken, note down why, skip to where the
+    /// next one probably starts, and give back nothing.
+    fn declaration(&mut self) -> Option<Stmt> {
         let res = {
-            if self.matches(vec![TokenType::VAR]) {
+            if self.matches(vec![TokenType::CLASS]) {
+                self.class_declaration()
+            } else if self.matches(vec![TokenType::FUN]) {
+                self.function("function")
+            } else if self.matches(vec![TokenType::VAR]) {
                 self.var_declaration()
             } else {
                 self.statement()
             }
         };
-        if res.is_ok() {
-            res
+        match res {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        let name = self.consume(TokenType::IDENTIFIER, "Expect class name.")?;
+
+        let superclass = if self.matches(vec![TokenType::LESS]) {
+            self.consume(TokenType::IDENTIFIER, "Expect superclass name.")?;
+            Some(Expr::Variable(self.previous().clone(), Cell::new(None)))
         } else {
-            self.synchronize();
-            Err(())
+            None
+        };
+
+        self.consume(TokenType::LEFT_BRACE, "Expect '{' before class body.")?;
+
+        let mut methods = vec![];
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RIGHT_BRACE, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class(keyword, name, superclass, methods))
+    }
+
+    /// `kind` is only there to make the error messages nicer.
+    fn function(&mut self, kind: &str) -> Result<Stmt, LoxError> {
+        // methods don't start with `fun`
+        let keyword = Some(self.previous())
+            .filter(|token| token.kind == TokenType::FUN)
+            .cloned();
+        let name = self.consume(TokenType::IDENTIFIER, &format!("Expect {} name.", kind))?;
+        self.consume(
+            TokenType::LEFT_PAREN,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+
+        let mut parameters = vec![];
+        if !self.check(TokenType::RIGHT_PAREN) {
+            loop {
+                if parameters.len() >= MAX_ARGUMENTS {
+                    // no need to bail, the parser isn't confused
+                    let error = self.error(self.peek(), "Can't have more than 255 parameters.");
+                    self.errors.push(error);
+                }
+                parameters.push(self.consume(TokenType::IDENTIFIER, "Expect parameter name.")?);
+                if !self.matches(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
         }
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LEFT_BRACE,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        let body = self.block()?;
+        Ok(Stmt::Function(keyword, name, parameters, Rc::new(body)))
     }
 
-    fn var_declaration(&mut self) -> Result<Stmt, ()> {
+    fn var_declaration(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
         let name = self.consume(TokenType::IDENTIFIER, "Expect variable name.")?;
 
         let mut initializer: Option<Expr> = None;
@@ -51,69 +148,228 @@ impl Parser {
             TokenType::SEMICOLON,
             "Expect ';' after variable declaration.",
         )?;
-        Ok(Stmt::Var(name, initializer))
+        Ok(Stmt::Var(keyword, name, initializer))
     }
 
-    fn statement(&mut self) -> Result<Stmt, ()> {
-        if self.matches(vec![TokenType::PRINT]) {
+    fn statement(&mut self) -> Result<Stmt, LoxError> {
+        if self.matches(vec![TokenType::FOR]) {
+            self.for_statement()
+        } else if self.matches(vec![TokenType::IF]) {
+            self.if_statement()
+        } else if self.matches(vec![TokenType::PRINT]) {
             self.print_statement()
+        } else if self.matches(vec![TokenType::RETURN]) {
+            self.return_statement()
+        } else if self.matches(vec![TokenType::WHILE]) {
+            self.while_statement()
+        } else if self.matches(vec![TokenType::LEFT_BRACE]) {
+            Ok(Stmt::Block(self.block()?))
         } else {
             self.expression_statement()
         }
     }
 
-    fn print_statement(&mut self) -> Result<Stmt, ()> {
+    /// There's no `Stmt::For`: a `for` loop is just sugar for a `while` loop
+    /// wrapped in a block with its initializer.
+    fn for_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.matches(vec![TokenType::SEMICOLON]) {
+            None
+        } else if self.matches(vec![TokenType::VAR]) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expression_statement()?)
+        };
+
+        let condition = if self.check(TokenType::SEMICOLON) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        let condition_end =
+            self.consume(TokenType::SEMICOLON, "Expect ';' after loop condition.")?;
+
+        let increment = if self.check(TokenType::RIGHT_PAREN) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after for clauses.")?;
+
+        let mut body = self.statement()?;
+
+        if let Some(increment) = increment {
+            body = Stmt::Block(vec![body, Stmt::Expression(increment)]);
+        }
+
+        // no condition means loop forever
+        let condition =
+            condition.unwrap_or_else(|| Expr::Literal(condition_end, Value::Bool(true)));
+        body = Stmt::While(keyword, condition, Box::new(body));
+
+        if let Some(initializer) = initializer {
+            body = Stmt::Block(vec![initializer, body]);
+        }
+
+        Ok(body)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after if condition.")?;
+
+        let then_branch = self.statement()?;
+        // a dangling else binds to the nearest if
+        let else_branch = if self.matches(vec![TokenType::ELSE]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        Ok(Stmt::If(
+            keyword,
+            condition,
+            Box::new(then_branch),
+            else_branch,
+        ))
+    }
+
+    fn print_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
         let value = self.expression()?;
         self.consume(TokenType::SEMICOLON, "Expect ';' after value.")?;
-        Ok(Stmt::Print(value))
+        Ok(Stmt::Print(keyword, value))
     }
 
-    fn expression_statement(&mut self) -> Result<Stmt, ()> {
+    fn expression_statement(&mut self) -> Result<Stmt, LoxError> {
+        let start = self.current;
         let expr = self.expression()?;
+        if self.check(TokenType::COLON) && self.block_start == Some(start) {
+            // most likely a map literal, which got taken for a block
+            let error = self.error(self.peek(), "Expect ';' after expression.");
+            return Err(error.with_help(
+                "a `{` starting a statement opens a block; wrap a map in parentheses to use it there",
+            ));
+        }
         self.consume(TokenType::SEMICOLON, "Expect ';' after expression.")?;
         Ok(Stmt::Expression(expr))
     }
 
-    fn expression(&mut self) -> Result<Expr, ()> {
+    fn return_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        let value = if self.check(TokenType::SEMICOLON) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+
+        self.consume(TokenType::SEMICOLON, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, LoxError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LEFT_PAREN, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RIGHT_PAREN, "Expect ')' after condition.")?;
+        let body = self.statement()?;
+
+        Ok(Stmt::While(keyword, condition, Box::new(body)))
+    }
+
+    /// Call this right after eating the opening '{'.
+    fn block(&mut self) -> Result<Vec<Stmt>, LoxError> {
+        let opening = self.previous().clone();
+        let mut statements = vec![];
+        self.block_start = Some(self.current);
+
+        // a broken statement inside the block doesn't take the whole block down with it
+        while !self.check(TokenType::RIGHT_BRACE) && !self.is_at_end() {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+
+        self.consume_closing(TokenType::RIGHT_BRACE, "Expect '}' after block.", &opening)?;
+        Ok(statements)
+    }
+
+    fn expression(&mut self) -> Result<Expr, LoxError> {
         self.assignment()
     }
 
-    fn assignment(&mut self) -> Result<Expr, ()> {
-        let expr = self.equality()?;
+    fn assignment(&mut self) -> Result<Expr, LoxError> {
+        let expr = self.or()?;
 
         if self.matches(vec![TokenType::EQUAL]) {
-            let equals = self.previous();
+            let equals = self.previous().clone();
             let value = self.assignment()?;
 
-            if let Expr::Variable(name) = expr {
-                return Ok(Expr::Assign(name, Box::new(value)));
+            match expr {
+                Expr::Variable(name, _) => {
+                    return Ok(Expr::Assign(name, Box::new(value), Cell::new(None)))
+                }
+                Expr::Get(object, name) => return Ok(Expr::Set(object, name, Box::new(value))),
+                Expr::Index(object, bracket, index) => {
+                    return Ok(Expr::SetIndex(object, bracket, index, Box::new(value)))
+                }
+                _ => {}
             }
 
-            crate::error_token(equals, "Invalid assignment target.");
+            let error = self.error(&equals, "Invalid assignment target.");
+            self.errors.push(error);
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.and()?;
+
+        while self.matches(vec![TokenType::OR]) {
+            let operator = self.previous().clone();
+            let right = self.and()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
         }
 
         Ok(expr)
     }
 
-    fn equality(&mut self) -> Result<Expr, ()> {
+    fn and(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.equality()?;
+
+        while self.matches(vec![TokenType::AND]) {
+            let operator = self.previous().clone();
+            let right = self.equality()?;
+            expr = Expr::Logical(Box::new(expr), operator, Box::new(right));
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> Result<Expr, LoxError> {
         use TokenType::*;
 
         let mut expr = self.comparison()?;
         while self.matches(vec![BANG_EQUAL, EQUAL_EQUAL]) {
-            let operator = self.previous();
+            let operator = self.previous().clone();
             let right = self.comparison()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
         Ok(expr)
     }
 
-    fn comparison(&mut self) -> Result<Expr, ()> {
+    fn comparison(&mut self) -> Result<Expr, LoxError> {
         use TokenType::*;
 
         let mut expr = self.addition()?;
 
         while self.matches(vec![GREATER, GREATER_EQUAL, LESS, LESS_EQUAL]) {
-            let operator = self.previous();
+            let operator = self.previous().clone();
             let right = self.addition()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -121,13 +377,13 @@ impl Parser {
         Ok(expr)
     }
 
-    fn addition(&mut self) -> Result<Expr, ()> {
+    fn addition(&mut self) -> Result<Expr, LoxError> {
         use TokenType::*;
 
         let mut expr = self.multiplication()?;
 
         while self.matches(vec![MINUS, PLUS]) {
-            let operator = self.previous();
+            let operator = self.previous().clone();
             let right = self.multiplication()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -135,13 +391,13 @@ impl Parser {
         Ok(expr)
     }
 
-    fn multiplication(&mut self) -> Result<Expr, ()> {
+    fn multiplication(&mut self) -> Result<Expr, LoxError> {
         use TokenType::*;
 
         let mut expr = self.unary()?;
 
         while self.matches(vec![SLASH, STAR]) {
-            let operator = self.previous();
+            let operator = self.previous().clone();
             let right = self.unary()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
@@ -149,50 +405,224 @@ impl Parser {
         Ok(expr)
     }
 
-    fn unary(&mut self) -> Result<Expr, ()> {
+    fn unary(&mut self) -> Result<Expr, LoxError> {
         use TokenType::*;
 
         if self.matches(vec![BANG, MINUS]) {
-            let operator = self.previous();
+            let operator = self.previous().clone();
             let right = self.unary()?;
             return Ok(Expr::Unary(operator, Box::new(right)));
         }
 
-        Ok(self.primary()?)
+        self.call()
+    }
+
+    fn call(&mut self) -> Result<Expr, LoxError> {
+        let mut expr = self.primary()?;
+
+        loop {
+            if self.matches(vec![TokenType::LEFT_PAREN]) {
+                expr = self.finish_call(expr)?;
+            } else if self.matches(vec![TokenType::DOT]) {
+                let name =
+                    self.consume(TokenType::IDENTIFIER, "Expect property name after '.'.")?;
+                expr = Expr::Get(Box::new(expr), name);
+            } else if self.matches(vec![TokenType::LEFT_BRACKET]) {
+                let opening = self.previous().clone();
+                let index = self.expression()?;
+                let bracket = self.consume_closing(
+                    TokenType::RIGHT_BRACKET,
+                    "Expect ']' after index.",
+                    &opening,
+                )?;
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index));
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, LoxError> {
+        let opening = self.previous().clone();
+        let mut arguments = vec![];
+        if !self.check(TokenType::RIGHT_PAREN) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let error = self.error(self.peek(), "Can't have more than 255 arguments.");
+                    self.errors.push(error);
+                }
+                arguments.push(self.expression()?);
+                if !self.matches(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = self.consume_closing(
+            TokenType::RIGHT_PAREN,
+            "Expect ')' after arguments.",
+            &opening,
+        )?;
+
+        Ok(Expr::Call(Box::new(callee), paren, arguments))
     }
 
-    fn primary(&mut self) -> Result<Expr, ()> {
+    fn primary(&mut self) -> Result<Expr, LoxError> {
         use TokenType::*;
 
         if self.matches(vec![FALSE]) {
-            Ok(Expr::Literal(Value::Bool(false)))
+            Ok(Expr::Literal(self.previous().clone(), Value::Bool(false)))
         } else if self.matches(vec![TRUE]) {
-            Ok(Expr::Literal(Value::Bool(true)))
+            Ok(Expr::Literal(self.previous().clone(), Value::Bool(true)))
         } else if self.matches(vec![NIL]) {
-            Ok(Expr::Literal(Value::Nil))
+            Ok(Expr::Literal(self.previous().clone(), Value::Nil))
         } else if self.matches(vec![NUMBER, STRING]) {
-            Ok(Expr::Literal(self.previous().literal))
+            let token = self.previous().clone();
+            let value = token.literal.clone();
+            Ok(Expr::Literal(token, value))
+        } else if self.matches(vec![INTERPOLATION]) {
+            self.interpolation()
+        } else if self.matches(vec![SUPER]) {
+            let keyword = self.previous().clone();
+            self.consume(DOT, "Expect '.' after 'super'.")?;
+            let method = self.consume(IDENTIFIER, "Expect superclass method name.")?;
+            Ok(Expr::Super(keyword, method, Cell::new(None)))
+        } else if self.matches(vec![THIS]) {
+            Ok(Expr::This(self.previous().clone(), Cell::new(None)))
         } else if self.matches(vec![IDENTIFIER]) {
-            Ok(Expr::Variable(self.previous()))
+            Ok(Expr::Variable(self.previous().clone(), Cell::new(None)))
         } else if self.matches(vec![LEFT_PAREN]) {
+            let opening = self.previous().clone();
             let expr = self.expression()?;
-            self.consume(RIGHT_PAREN, "Expect ')' after expression.")?;
+            self.consume_closing(RIGHT_PAREN, "Expect ')' after expression.", &opening)?;
             Ok(Expr::Grouping(Box::new(expr)))
+        } else if self.matches(vec![LEFT_BRACKET]) {
+            self.list()
+        } else if self.matches(vec![LEFT_BRACE]) {
+            self.map()
         } else {
-            error(self.peek(), "Expect expression.");
-            Err(())
+            Err(self.error(self.peek(), "Expect expression."))
         }
     }
 
-    fn consume(&mut self, expected: TokenType, message: &str) -> Result<Token, ()> {
+    /// A list literal, like `[1, 2, 3]`. The opening bracket has just been matched.
+    fn list(&mut self) -> Result<Expr, LoxError> {
+        let opening = self.previous().clone();
+        let mut elements = vec![];
+        if !self.check(TokenType::RIGHT_BRACKET) {
+            loop {
+                if elements.len() >= MAX_ELEMENTS {
+                    // no need to bail, the parser isn't confused
+                    let error = self.error(self.peek(), "Too many elements in list literal.");
+                    self.errors.push(error);
+                }
+                elements.push(self.expression()?);
+                if !self.matches(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
+        let closing = self.consume_closing(
+            TokenType::RIGHT_BRACKET,
+            "Expect ']' after list elements.",
+            &opening,
+        )?;
+        Ok(Expr::List(opening, elements, closing))
+    }
+
+    /// A map literal, like `{"a": 1, "b": 2}`. A `{` only starts a map where an
+    /// expression is expected. At the start of a statement, it's a block.
+    fn map(&mut self) -> Result<Expr, LoxError> {
+        let opening = self.previous().clone();
+        let mut keys = vec![];
+        let mut values = vec![];
+        if !self.check(TokenType::RIGHT_BRACE) {
+            loop {
+                if keys.len() >= MAX_ELEMENTS {
+                    // no need to bail, the parser isn't confused
+                    let error = self.error(self.peek(), "Too many entries in map literal.");
+                    self.errors.push(error);
+                }
+                keys.push(self.expression()?);
+                self.consume(TokenType::COLON, "Expect ':' after map key.")?;
+                values.push(self.expression()?);
+                if !self.matches(vec![TokenType::COMMA]) {
+                    break;
+                }
+            }
+        }
+
+        let closing = self.consume_closing(
+            TokenType::RIGHT_BRACE,
+            "Expect '}' after map entries.",
+            &opening,
+        )?;
+        Ok(Expr::Map(opening, keys, values, closing))
+    }
+
+    /// A string with expressions spliced into it. The scanner hands it over as
+    /// an `INTERPOLATION` token for each bit of text before a `${`, each
+    /// followed by the tokens of the expression, then a `STRING` for the rest.
+    fn interpolation(&mut self) -> Result<Expr, LoxError> {
+        let start = self.previous().clone();
+        let mut parts = vec![];
+        loop {
+            let text = self.previous().clone();
+            let opening = text.span;
+            let value = text.literal.clone();
+            parts.push(Expr::Literal(text, value));
+
+            // with nothing between `${` and `}`, the rest of the string comes
+            // straight away. it'd parse as a string on its own, so catch it here
+            let next = self.peek();
+            let rest_of_string = matches!(next.kind, TokenType::STRING | TokenType::INTERPOLATION)
+                && next.lexeme.with_str(|lexeme| lexeme.starts_with('}'));
+            if rest_of_string {
+                let error = self.error(next, "Expect expression.");
+                return Err(error.with_label(opening, "interpolation starts here"));
+            }
+            if parts.len() + 2 > MAX_PIECES {
+                // no need to bail, the parser isn't confused
+                let error = self.error(next, "Too many pieces in interpolated string.");
+                self.errors.push(error);
+            }
+            parts.push(self.expression()?);
+
+            if self.matches(vec![TokenType::STRING]) {
+                let text = self.previous().clone();
+                let value = text.literal.clone();
+                parts.push(Expr::Literal(text, value));
+                return Ok(Expr::Interpolation(start, parts));
+            }
+            if !self.matches(vec![TokenType::INTERPOLATION]) {
+                let error = self.error(self.peek(), "Expect '}' after interpolated expression.");
+                return Err(error.with_label(opening, "interpolation starts here"));
+            }
+        }
+    }
+
+    fn consume(&mut self, expected: TokenType, message: &str) -> Result<Token, LoxError> {
         if self.check(expected) {
-            Ok(self.advance())
+            Ok(self.advance().clone())
         } else {
-            error(self.peek(), message);
-            Err(())
+            Err(self.error(self.peek(), message))
         }
     }
 
+    /// Like `consume`, but points back at the bracket that never got closed.
+    fn consume_closing(
+        &mut self,
+        expected: TokenType,
+        message: &str,
+        opening: &Token,
+    ) -> Result<Token, LoxError> {
+        self.consume(expected, message)
+            .map_err(|error| error.with_label(opening.span, "unclosed delimiter"))
+    }
+
     fn matches(&mut self, types: Vec<TokenType>) -> bool {
         for ty in types {
             if self.check(ty) {
@@ -211,7 +641,7 @@ impl Parser {
         }
     }
 
-    fn advance(&mut self) -> Token {
+    fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
         }
@@ -222,12 +652,44 @@ impl Parser {
         self.peek().kind == TokenType::EOF
     }
 
-    fn peek(&self) -> Token {
-        self.tokens[self.current].clone()
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
     }
 
-    fn previous(&self) -> Token {
-        self.tokens[self.current - 1].clone()
+    fn previous(&self) -> &Token {
+        &self.tokens[self.current - 1]
+    }
+
+    /// Make an error at `token`. The caller decides whether it's bad enough to
+    /// bail out over, or whether to note it down in `errors` and carry on.
+    ///
+    /// If a word from some other language is nearby, suggest the Lox one.
+    fn error(&self, token: &Token, message: &str) -> LoxError {
+        let mut nearby = vec![token];
+        if self.current > 0 {
+            nearby.push(self.previous());
+        }
+        let hint = nearby.into_iter().find_map(|nearby| {
+            if nearby.kind != TokenType::IDENTIFIER {
+                return None;
+            }
+            nearby.lexeme.with_str(suggestion).map(|lox| (nearby, lox))
+        });
+
+        let error = LoxError::Parse {
+            token: token.clone(),
+            message: message.to_string(),
+            notes: Box::default(),
+        };
+        match hint {
+            Some((nearby, lox)) => error
+                .with_label(
+                    nearby.span,
+                    format!("this is a variable called `{}`", nearby.lexeme),
+                )
+                .with_help(format!("did you mean `{}`?", lox)),
+            None => error,
+        }
     }
 
     fn synchronize(&mut self) {
@@ -252,7 +714,3 @@ impl Parser {
         }
     }
 }
-
-fn error(token: Token, message: &str) {
-    crate::error_token(token, message);
-}
@@ -0,0 +1,343 @@
+use crate::{
+    ast::{
+        expr::{self, Expr},
+        stmt::{self, Stmt},
+    },
+    error::LoxError,
+    literal::Value,
+    symbol::Symbol,
+    token::{Span, Token},
+};
+use std::{cell::Cell, collections::HashMap, rc::Rc};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+/// A local variable the resolver knows about.
+#[derive(Debug, Copy, Clone)]
+struct Local {
+    /// Whether the variable's initializer has finished.
+    defined: bool,
+    /// Where it was declared.
+    span: Span,
+}
+
+impl Local {
+    fn new(defined: bool, span: Span) -> Self {
+        Self { defined, span }
+    }
+}
+
+/// A static pass that runs between the parser and the interpreter. It works out
+/// how many scopes out each local variable lives, so the interpreter doesn't
+/// have to go looking by name, and catches the mistakes that don't need the
+/// program to run to spot.
+pub struct Resolver {
+    /// Local scopes only; globals aren't tracked.
+    scopes: Vec<HashMap<Symbol, Local>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<LoxError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![],
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors: vec![],
+        }
+    }
+
+    /// Resolve a whole program, or hand back every error found in it.
+    pub fn resolve(&mut self, statements: &[Stmt]) -> Result<(), Vec<LoxError>> {
+        self.resolve_stmts(statements);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
+    }
+
+    fn resolve_stmts(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            statement.accept(self);
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        expr.accept(self);
+    }
+
+    fn resolve_function(&mut self, params: &[Token], body: &[Stmt], kind: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = kind;
+
+        self.begin_scope();
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    /// Record how far out `name` was found, if it's a local at all.
+    fn resolve_local(&mut self, name: &Token, depth: &Cell<Option<usize>>) {
+        if let Some(distance) = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.name()))
+        {
+            depth.set(Some(distance));
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        let error = Self::resolve_error(token, message);
+        self.errors.push(error);
+    }
+
+    fn resolve_error(token: &Token, message: &str) -> LoxError {
+        LoxError::Resolve {
+            token: token.clone(),
+            message: message.to_string(),
+            notes: Box::default(),
+        }
+    }
+
+    fn declare(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            let local = Local::new(false, name.span);
+            if let Some(previous) = scope.insert(name.name(), local) {
+                let error =
+                    Self::resolve_error(name, "Already a variable with this name in this scope.")
+                        .with_label(previous.span, "first declared here");
+                self.errors.push(error);
+            }
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.name(), Local::new(true, name.span));
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl expr::Visitor<()> for Resolver {
+    fn visit_assign_expr(&mut self, a0: &Token, a1: &Expr, a2: &Cell<Option<usize>>) {
+        self.resolve_expr(a1);
+        self.resolve_local(a0, a2);
+    }
+    fn visit_binary_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr) {
+        self.resolve_expr(a0);
+        self.resolve_expr(a2);
+    }
+    fn visit_call_expr(&mut self, a0: &Expr, _a1: &Token, a2: &[Expr]) {
+        self.resolve_expr(a0);
+        for argument in a2 {
+            self.resolve_expr(argument);
+        }
+    }
+    fn visit_get_expr(&mut self, a0: &Expr, _a1: &Token) {
+        // properties are looked up dynamically, only the object gets resolved
+        self.resolve_expr(a0);
+    }
+    fn visit_grouping_expr(&mut self, a0: &Expr) {
+        self.resolve_expr(a0);
+    }
+    fn visit_index_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr) {
+        self.resolve_expr(a0);
+        self.resolve_expr(a2);
+    }
+    fn visit_interpolation_expr(&mut self, _a0: &Token, a1: &[Expr]) {
+        for part in a1 {
+            self.resolve_expr(part);
+        }
+    }
+    fn visit_list_expr(&mut self, _a0: &Token, a1: &[Expr], _a2: &Token) {
+        for element in a1 {
+            self.resolve_expr(element);
+        }
+    }
+    fn visit_literal_expr(&mut self, _a0: &Token, _a1: &Value) {}
+    fn visit_map_expr(&mut self, _a0: &Token, a1: &[Expr], a2: &[Expr], _a3: &Token) {
+        for (key, value) in a1.iter().zip(a2) {
+            self.resolve_expr(key);
+            self.resolve_expr(value);
+        }
+    }
+    fn visit_logical_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr) {
+        self.resolve_expr(a0);
+        self.resolve_expr(a2);
+    }
+    fn visit_set_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr) {
+        self.resolve_expr(a2);
+        self.resolve_expr(a0);
+    }
+    fn visit_setindex_expr(&mut self, a0: &Expr, _a1: &Token, a2: &Expr, a3: &Expr) {
+        self.resolve_expr(a0);
+        self.resolve_expr(a2);
+        self.resolve_expr(a3);
+    }
+    fn visit_super_expr(&mut self, a0: &Token, _a1: &Token, a2: &Cell<Option<usize>>) {
+        match self.current_class {
+            ClassType::None => self.error(a0, "Can't use 'super' outside of a class."),
+            ClassType::Class => self.error(a0, "Can't use 'super' in a class with no superclass."),
+            ClassType::Subclass => {}
+        }
+        self.resolve_local(a0, a2);
+    }
+    fn visit_this_expr(&mut self, a0: &Token, a1: &Cell<Option<usize>>) {
+        if self.current_class == ClassType::None {
+            self.error(a0, "Can't use 'this' outside of a class.");
+            return;
+        }
+        self.resolve_local(a0, a1);
+    }
+    fn visit_unary_expr(&mut self, _a0: &Token, a1: &Expr) {
+        self.resolve_expr(a1);
+    }
+    fn visit_variable_expr(&mut self, a0: &Token, a1: &Cell<Option<usize>>) {
+        let local = self.scopes.last().and_then(|scope| scope.get(&a0.name()));
+        if let Some(Local { defined: false, .. }) = local {
+            self.error(a0, "Can't read local variable in its own initializer.");
+        }
+        self.resolve_local(a0, a1);
+    }
+}
+
+impl stmt::Visitor<()> for Resolver {
+    fn visit_block_stmt(&mut self, a0: &[Stmt]) {
+        self.begin_scope();
+        self.resolve_stmts(a0);
+        self.end_scope();
+    }
+    fn visit_class_stmt(&mut self, _a0: &Token, a1: &Token, a2: &Option<Expr>, a3: &[Stmt]) {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(a1);
+        self.define(a1);
+
+        if let Some(superclass) = a2 {
+            if let Expr::Variable(name, _) = superclass {
+                if name.lexeme == a1.lexeme {
+                    self.error(name, "A class can't inherit from itself.");
+                }
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass);
+
+            // mirrors the extra scope the interpreter makes to hold `super`
+            self.begin_scope();
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert(Symbol::SUPER, Local::new(true, a1.span));
+        }
+
+        self.begin_scope();
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(Symbol::THIS, Local::new(true, a1.span));
+
+        for method in a3 {
+            if let Stmt::Function(_, name, params, body) = method {
+                let kind = if name.name() == Symbol::INIT {
+                    FunctionType::Initializer
+                } else {
+                    FunctionType::Method
+                };
+                self.resolve_function(params, body, kind);
+            }
+        }
+
+        self.end_scope();
+        if a2.is_some() {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+    fn visit_expression_stmt(&mut self, a0: &Expr) {
+        self.resolve_expr(a0);
+    }
+    fn visit_function_stmt(
+        &mut self,
+        _a0: &Option<Token>,
+        a1: &Token,
+        a2: &[Token],
+        a3: &Rc<Vec<Stmt>>,
+    ) {
+        // defined straight away, so the function can call itself
+        self.declare(a1);
+        self.define(a1);
+        self.resolve_function(a2, a3, FunctionType::Function);
+    }
+    fn visit_if_stmt(&mut self, _a0: &Token, a1: &Expr, a2: &Stmt, a3: &Option<Box<Stmt>>) {
+        self.resolve_expr(a1);
+        a2.accept(self);
+        if let Some(else_branch) = a3 {
+            else_branch.accept(self);
+        }
+    }
+    fn visit_print_stmt(&mut self, _a0: &Token, a1: &Expr) {
+        self.resolve_expr(a1);
+    }
+    fn visit_return_stmt(&mut self, a0: &Token, a1: &Option<Expr>) {
+        if self.current_function == FunctionType::None {
+            self.error(a0, "Can't return from top-level code.");
+        }
+        if let Some(value) = a1 {
+            if self.current_function == FunctionType::Initializer {
+                self.error(a0, "Can't return a value from an initializer.");
+            }
+            self.resolve_expr(value);
+        }
+    }
+    fn visit_var_stmt(&mut self, _a0: &Token, a1: &Token, a2: &Option<Expr>) {
+        // declared and defined separately, so `var a = a;` can be caught
+        self.declare(a1);
+        if let Some(initializer) = a2 {
+            self.resolve_expr(initializer);
+        }
+        self.define(a1);
+    }
+    fn visit_while_stmt(&mut self, _a0: &Token, a1: &Expr, a2: &Stmt) {
+        self.resolve_expr(a1);
+        a2.accept(self);
+    }
+}
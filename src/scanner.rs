@@ -1,4 +1,11 @@
-use crate::{literal::Value, token::Token, token_type::TokenType};
+use crate::{
+    diagnostic::suggestion,
+    error::LoxError,
+    literal::Value,
+    token::{Lexeme, Span, Token},
+    token_type::TokenType,
+};
+use std::{collections::VecDeque, rc::Rc};
 
 fn keyword(text: &str) -> Option<TokenType> {
     match text {
@@ -22,95 +29,142 @@ fn keyword(text: &str) -> Option<TokenType> {
     }
 }
 
-/// The Lox lexer!
-pub struct Scanner {
-    /// the source code, split into chars for easy Unicode handling 🕶
-    source: Vec<char>,
-    /// the parsed tokens
-    tokens: Vec<Token>,
+/// The Lox lexer! It's also an iterator over the tokens it finds, ending with
+/// an `EOF` token.
+pub struct Scanner<'a> {
+    /// the source code. it's walked a byte at a time, since everything Lox
+    /// cares about is ASCII, and anything else gets decoded as it comes up 🕶
+    source: &'a str,
+    /// whether the `EOF` token has been handed out yet
+    finished: bool,
+    /// errors found along with a token, like bad escapes in a string,
+    /// waiting to be handed out after it
+    pending: VecDeque<LoxError>,
+    /// one entry for every `${` the lexer is inside, counting the `{`s opened
+    /// since then. the `}` that takes it below zero goes back to the string
+    interpolations: Vec<usize>,
 
-    /// where the current source code token started
+    /// the byte offset where the current source code token started
     start: usize,
-    /// where the lexer is right now
+    /// the byte offset the lexer is at right now
     current: usize,
     line: usize,
+    /// the column (in chars) the lexer is at right now
+    column: usize,
+    /// the column the current token started on
+    start_column: usize,
+    /// how far into some bigger text the source code starts. spans are
+    /// shifted along by this much
+    offset: usize,
 }
 
-impl Scanner {
-    /// Create a lexer for this source code. Call `scan_tokens` after this if you want the lexer to run.
-    pub fn new<S>(source: S) -> Self
-    where
-        S: AsRef<str>,
-    {
+impl<'a> Scanner<'a> {
+    /// Create a lexer for this source code. Iterate over it, or call
+    /// `scan_tokens`, if you want the lexer to run.
+    pub fn new(source: &'a str) -> Self {
+        Self::with_offset(source, 0)
+    }
+
+    /// A lexer for source code that starts `offset` bytes into some bigger
+    /// text, like everything typed into a REPL so far. The spans of its tokens
+    /// and errors point into that text rather than into `source`.
+    pub fn with_offset(source: &'a str, offset: usize) -> Self {
         Self {
-            source: source.as_ref().chars().collect(),
-            tokens: vec![],
+            source,
+            finished: false,
+            pending: VecDeque::new(),
+            interpolations: vec![],
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_column: 1,
+            offset,
         }
     }
 
-    /// "Do your job!" Returns the tokens it lexed.
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    /// "Do your job!" Returns the tokens it lexed, plus any errors it ran into.
+    /// The tokens are still worth parsing even if there were errors.
+    pub fn scan_tokens(self) -> (Vec<Token>, Vec<LoxError>) {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        for result in self {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
         }
-
-        self.tokens.push(Token::new(
-            TokenType::EOF,
-            "".to_string(),
-            Value::Nil,
-            self.line,
-        ));
-        self.tokens.clone()
+        (tokens, errors)
     }
 
     fn is_at_end(&self) -> bool {
         self.current >= self.source.len()
     }
 
-    /// Scan one token. This is the meat of the lexer.
-    fn scan_token(&mut self) {
+    /// Scan one token. This is the meat of the lexer. Whitespace and
+    /// comments don't make a token, so they hand back `None`.
+    fn scan_token(&mut self) -> Option<Result<Token, LoxError>> {
         use TokenType::*;
-        match self.advance() {
+        let kind = match self.advance() {
             // basic tokens
-            '(' => self.add_token(LEFT_PAREN, Value::Nil),
-            ')' => self.add_token(RIGHT_PAREN, Value::Nil),
-            '{' => self.add_token(LEFT_BRACE, Value::Nil),
-            '}' => self.add_token(RIGHT_BRACE, Value::Nil),
-            ',' => self.add_token(COMMA, Value::Nil),
-            '.' => self.add_token(DOT, Value::Nil),
-            '-' => self.add_token(MINUS, Value::Nil),
-            '+' => self.add_token(PLUS, Value::Nil),
-            ';' => self.add_token(SEMICOLON, Value::Nil),
-            '*' => self.add_token(STAR, Value::Nil),
+            '(' => LEFT_PAREN,
+            ')' => RIGHT_PAREN,
+            '[' => LEFT_BRACKET,
+            ']' => RIGHT_BRACKET,
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                LEFT_BRACE
+            }
+            '}' => match self.interpolations.last_mut() {
+                // the end of an interpolated expression, so back to the string
+                Some(0) => {
+                    self.interpolations.pop();
+                    return Some(self.string());
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    RIGHT_BRACE
+                }
+                None => RIGHT_BRACE,
+            },
+            ':' => COLON,
+            ',' => COMMA,
+            '.' => DOT,
+            '-' => MINUS,
+            '+' => PLUS,
+            ';' => SEMICOLON,
+            '*' => STAR,
 
             // two-char tokens like <=
             '!' => {
-                let kind = if self.matches('=') { BANG_EQUAL } else { BANG };
-                self.add_token(kind, Value::Nil);
+                if self.matches('=') {
+                    BANG_EQUAL
+                } else {
+                    BANG
+                }
             }
             '=' => {
-                let kind = if self.matches('=') {
+                if self.matches('=') {
                     EQUAL_EQUAL
                 } else {
                     EQUAL
-                };
-                self.add_token(kind, Value::Nil);
+                }
             }
             '<' => {
-                let kind = if self.matches('=') { LESS_EQUAL } else { LESS };
-                self.add_token(kind, Value::Nil);
+                if self.matches('=') {
+                    LESS_EQUAL
+                } else {
+                    LESS
+                }
             }
             '>' => {
-                let kind = if self.matches('=') {
+                if self.matches('=') {
                     GREATER_EQUAL
                 } else {
                     GREATER
-                };
-                self.add_token(kind, Value::Nil);
+                }
             }
 
             // slash token, or line comment? you decide, gamers
@@ -120,118 +174,331 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
-                } else {
-                    self.add_token(SLASH, Value::Nil);
+                    return None;
+                }
+                if self.matches('*') {
+                    return self.block_comment().map(Err);
                 }
+                SLASH
             }
 
             // inferior characters, they don't even count LUL
-            ' ' | '\r' | '\t' => {}
+            ' ' | '\r' | '\t' => return None,
             '\n' => {
                 // well, this one kind of does stuff
-                self.line += 1;
+                self.newline();
+                return None;
             }
 
             // special shit like strings and other literals
-            '"' => self.string(),
-            c if c.is_numeric() => self.number(),
-            c if c.is_alphabetic() => self.identifier(),
+            '"' => return Some(self.string()),
+            c if c.is_ascii_digit() => return Some(Ok(self.number())),
+            c if c.is_alphabetic() || c == '_' => return Some(Ok(self.identifier())),
 
             // oops!
-            _ => crate::error(self.line, "Unexpected character."),
-        }
+            c => {
+                let mut error = self.error("Unexpected character.");
+                if let Some(lox) = suggestion(c.encode_utf8(&mut [0; 4])) {
+                    error = error.with_help(format!("Lox spells this operator `{}`", lox));
+                }
+                return Some(Err(error));
+            }
+        };
+        Some(Ok(self.token(kind, Value::Nil)))
     }
 
-    fn identifier(&mut self) {
-        while self.peek().is_alphanumeric() {
-            self.advance();
+    /// Skip a `/* ... */` comment, which can have more of them nested inside.
+    /// If it never ends, the error points back at where it started.
+    fn block_comment(&mut self) -> Option<LoxError> {
+        let line = self.line;
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                let error = LoxError::Lex {
+                    line,
+                    span: self.span(self.start, self.start + 2),
+                    message: "Unterminated block comment.".to_string(),
+                    notes: Box::default(),
+                };
+                return Some(if depth > 1 {
+                    error.with_help("block comments nest, so every `/*` needs its own `*/`")
+                } else {
+                    error
+                });
+            }
+
+            match self.advance() {
+                '/' if self.peek() == '*' => {
+                    self.advance();
+                    depth += 1;
+                }
+                '*' if self.peek() == '/' => {
+                    self.advance();
+                    depth -= 1;
+                }
+                '\n' => self.newline(),
+                _ => {}
+            }
         }
-        let text = self.source[self.start..self.current]
-            .iter()
-            .collect::<String>();
-        let kind = keyword(&text).unwrap_or(TokenType::IDENTIFIER);
-        self.add_token(kind, Value::Nil);
+        None
     }
 
-    fn number(&mut self) {
-        while self.peek().is_numeric() {
+    fn identifier(&mut self) -> Token {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
+        let kind = keyword(self.lexeme()).unwrap_or(TokenType::IDENTIFIER);
+        self.token(kind, Value::Nil)
+    }
+
+    /// Scan a number literal: decimal, maybe with a fraction and an exponent,
+    /// or hex, octal or binary after a `0x`, `0o` or `0b`. Any of them can
+    /// have `_`s between digits to make them easier to read.
+    fn number(&mut self) -> Token {
+        let radix = match (self.lexeme(), self.peek()) {
+            ("0", 'x') | ("0", 'X') => Some((16, "hex")),
+            ("0", 'o') | ("0", 'O') => Some((8, "octal")),
+            ("0", 'b') | ("0", 'B') => Some((2, "binary")),
+            _ => None,
+        };
+        let value = match radix {
+            Some((radix, name)) => self.radix_number(radix, name),
+            None => self.decimal_number(),
+        };
+
+        // a bad literal is still a number as far as the parser cares, so it
+        // doesn't go on to complain about a missing expression
+        let value = value.unwrap_or_else(|error| {
+            self.pending.push_back(error);
+            0.0
+        });
+        self.token(TokenType::NUMBER, Value::Number(value))
+    }
 
-        if self.peek() == '.' && self.peek_next().is_numeric() {
+    fn decimal_number(&mut self) -> Result<f64, LoxError> {
+        self.digits()?;
+
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             // floating-point literal
 
             // consume the dot
             self.advance();
             // more numbers!
-            while self.peek().is_numeric() {
+            self.digits()?;
+        }
+
+        if self.peek() == 'e' || self.peek() == 'E' {
+            self.advance();
+            if self.peek() == '+' || self.peek() == '-' {
                 self.advance();
             }
+            if !self.peek().is_ascii_digit() {
+                // skip whatever it was, so it doesn't turn into more errors
+                while self.peek().is_alphanumeric() || self.peek() == '_' {
+                    self.advance();
+                }
+                return Err(self
+                    .error("Expect digits after exponent.")
+                    .with_help("write it like 1e9 or 2.5e-3"));
+            }
+            self.digits()?;
+        }
+
+        self.lexeme()
+            .replace('_', "")
+            .parse::<f64>()
+            .map_err(|_| self.error("Invalid number."))
+    }
+
+    /// The digits of a hex, octal or binary literal, after the leading `0`.
+    fn radix_number(&mut self, radix: u32, name: &str) -> Result<f64, LoxError> {
+        // consume the x, o or b
+        self.advance();
+        let digits_start = self.current;
+        // take in anything that looks like it's part of the number, so a bad
+        // digit gets reported rather than turning into the start of a new token
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+
+        if digits.is_empty() {
+            let message = format!("Expect {} digits after '{}'.", name, self.lexeme());
+            return Err(self.error(&message));
+        }
+        if let Some(bad) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            let message = format!("Invalid digit '{}' in {} literal.", bad, name);
+            return Err(self.error(&message));
+        }
+        if digits.starts_with('_') || digits.ends_with('_') {
+            return Err(self.error("Digit separators go between digits."));
         }
 
-        self.add_token(
-            TokenType::NUMBER,
-            Value::Number(
-                self.source[self.start..self.current]
-                    .iter()
-                    .collect::<String>()
-                    .parse::<f64>()
-                    // it'll parse for sure, we already validated it
-                    .expect("Oh god oh fuck"),
-            ),
-        );
+        // too big for a u64 is fine, it just gets less precise like any other f64
+        Ok(digits
+            .chars()
+            .filter_map(|c| c.to_digit(radix))
+            .fold(0.0, |value, digit| value * radix as f64 + digit as f64))
     }
 
-    fn string(&mut self) {
+    /// Consume a run of decimal digits, which may have `_`s between them. The
+    /// first digit is either already consumed or known to be there.
+    fn digits(&mut self) -> Result<(), LoxError> {
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            if self.advance() == '_' && !self.peek().is_ascii_digit() && self.peek() != '_' {
+                return Err(self.error("Digit separators go between digits."));
+            }
+        }
+        Ok(())
+    }
+
+    /// Scan the text of a string, from just after the opening `"` (or the `}`
+    /// of an interpolation) up to the closing `"` (or the next `${`).
+    fn string(&mut self) -> Result<Token, LoxError> {
+        let text_start = self.current;
+        // the text with escapes decoded. most strings don't have any, so this
+        // only gets built once one turns up
+        let mut decoded: Option<String> = None;
+        // where the source text not yet copied into `decoded` starts
+        let mut copied_up_to = self.current;
+        let mut kind = TokenType::STRING;
+
         // look for closing "
         while self.peek() != '"' && !self.is_at_end() {
-            // yeah, still keeping track of line #'s here
-            if self.peek() == '\n' {
-                self.line += 1;
+            if self.peek() == '$' && self.peek_next() == '{' {
+                kind = TokenType::INTERPOLATION;
+                break;
+            } else if self.peek() == '\\' {
+                let text = decoded.get_or_insert_with(String::new);
+                text.push_str(&self.source[copied_up_to..self.current]);
+                match self.escape() {
+                    Some(Ok(c)) => text.push(c),
+                    Some(Err(error)) => self.pending.push_back(error),
+                    // a backslash right at the end, which is unterminated anyway
+                    None => {}
+                }
+                copied_up_to = self.current;
+            } else if self.advance() == '\n' {
+                // yeah, still keeping track of line #'s here
+                self.newline();
             }
-            self.advance();
         }
 
         // you forgot the closing "
         if self.is_at_end() {
-            crate::error(self.line, "Unterminated string.");
-            return;
+            return Err(self.error("Unterminated string."));
+        }
+
+        let text_end = self.current;
+        if kind == TokenType::INTERPOLATION {
+            // the expression inside gets scanned as normal tokens
+            self.advance();
+            self.advance();
+            self.interpolations.push(0);
+        } else {
+            self.advance();
         }
 
+        // the literal here is the source code with quotes stripped and escapes
+        // decoded
+        let value: Rc<str> = match decoded {
+            Some(mut text) => {
+                text.push_str(&self.source[copied_up_to..text_end]);
+                Rc::from(text)
+            }
+            None => Rc::from(&self.source[text_start..text_end]),
+        };
+        Ok(self.token(kind, Value::String(value)))
+    }
+
+    /// Decode the escape sequence the lexer is sitting on, backslash and all.
+    fn escape(&mut self) -> Option<Result<char, LoxError>> {
+        let start = self.current;
         self.advance();
+        if self.is_at_end() {
+            return None;
+        }
 
-        // the literal here is just the source code with quotes stripped
-        let value: String = self.source[self.start + 1..self.current - 1]
-            .iter()
-            .collect();
-        self.add_token(TokenType::STRING, Value::String(value));
+        let c = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '\\' => '\\',
+            '"' => '"',
+            '$' => '$',
+            '0' => '\0',
+            'u' => return Some(self.unicode_escape(start)),
+            c => {
+                if c == '\n' {
+                    self.newline();
+                }
+                let error = self
+                    .error_from(start, "Invalid escape sequence.")
+                    .with_help("the escapes are \\n, \\t, \\r, \\\\, \\\", \\$, \\0 and \\u{...}");
+                return Some(Err(error));
+            }
+        };
+        Some(Ok(c))
+    }
+
+    /// The rest of a `\u{XXXX}` escape, after the `u`.
+    fn unicode_escape(&mut self, start: usize) -> Result<char, LoxError> {
+        let invalid = |scanner: &Self, help: String| {
+            Err(scanner
+                .error_from(start, "Invalid Unicode escape.")
+                .with_help(help))
+        };
+        let expected = "write it like \\u{1F600}, with 1 to 6 hex digits".to_string();
+
+        if !self.matches('{') {
+            return invalid(self, expected);
+        }
+        let digits_start = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let digits = &self.source[digits_start..self.current];
+        if !self.matches('}') || digits.is_empty() || digits.len() > 6 {
+            return invalid(self, expected);
+        }
+
+        let code = u32::from_str_radix(digits, 16).expect("already checked they're hex digits");
+        match std::char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => invalid(self, format!("U+{:X} isn't a Unicode character", code)),
+        }
+    }
+
+    /// The char starting at byte `offset`, or '\0' past the end. ASCII is by
+    /// far the most common, so it skips decoding for that.
+    fn char_at(&self, offset: usize) -> char {
+        match self.source.as_bytes().get(offset) {
+            None => '\0',
+            Some(&byte) if byte.is_ascii() => byte as char,
+            Some(_) => self.source[offset..].chars().next().unwrap(),
+        }
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
+        if self.is_at_end() {
             '\0'
         } else {
-            self.source[self.current + 1]
+            self.char_at(self.current + self.peek().len_utf8())
         }
     }
 
     /// Have a look at the next character without consuming it
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            // dumy
-            '\0'
-        } else {
-            self.source[self.current]
-        }
+        self.char_at(self.current)
     }
 
     /// Have a look at the next character. If it matches the one provided, consume it.
     fn matches(&mut self, c: char) -> bool {
-        if self.is_at_end() || self.source[self.current] != c {
+        if self.is_at_end() || self.peek() != c {
             false
         } else {
             // c o n s u m e
-            self.current += 1;
+            self.advance();
             true
         }
     }
@@ -239,13 +506,79 @@ impl Scanner {
     /// Eat one character up. Yum.
     fn advance(&mut self) -> char {
         // c h o n c h
-        self.current += 1;
-        self.source[self.current - 1]
+        let c = self.char_at(self.current);
+        self.current += c.len_utf8();
+        self.column += 1;
+        c
+    }
+
+    /// Call this right after eating a '\n'.
+    fn newline(&mut self) {
+        self.line += 1;
+        self.column = 1;
+    }
+
+    /// The source code of the token scanned so far.
+    fn lexeme(&self) -> &'a str {
+        &self.source[self.start..self.current]
+    }
+
+    /// Oh no! An error covering the token scanned so far. Keep going though,
+    /// there might be more errors to find.
+    fn error(&self, message: &str) -> LoxError {
+        self.error_from(self.start, message)
+    }
+
+    /// An error covering everything from byte `start` up to where the lexer is.
+    fn error_from(&self, start: usize, message: &str) -> LoxError {
+        LoxError::Lex {
+            line: self.line,
+            span: self.span(start, self.current),
+            message: message.to_string(),
+            notes: Box::default(),
+        }
+    }
+
+    /// The span between two byte offsets into the source code.
+    fn span(&self, start: usize, end: usize) -> Span {
+        Span::new(self.offset + start, self.offset + end)
     }
 
     /// We finished parsing a token!
-    fn add_token(&mut self, kind: TokenType, literal: Value) {
-        let text: String = self.source[self.start..self.current].iter().collect();
-        self.tokens.push(Token::new(kind, text, literal, self.line));
+    fn token(&self, kind: TokenType, literal: Value) -> Token {
+        Token::new(
+            kind,
+            Lexeme::new(kind, self.lexeme()),
+            literal,
+            self.line,
+            self.start_column,
+            self.span(self.start, self.current),
+        )
+    }
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = Result<Token, LoxError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.pending.pop_front() {
+            return Some(Err(error));
+        }
+
+        while !self.is_at_end() {
+            self.start = self.current;
+            self.start_column = self.column;
+            if let Some(result) = self.scan_token() {
+                return Some(result);
+            }
+        }
+
+        if self.finished {
+            return None;
+        }
+        self.finished = true;
+        self.start = self.current;
+        self.start_column = self.column;
+        Some(Ok(self.token(TokenType::EOF, Value::Nil)))
     }
 }
@@ -0,0 +1,109 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Display},
+    marker::PhantomData,
+    rc::Rc,
+};
+
+/// A handle to an interned string. The same string always gets the same
+/// symbol, so comparing and hashing symbols is as cheap as it is for integers.
+///
+/// Each thread has its own interner, so symbols only mean something on the
+/// thread that made them. They're neither `Send` nor `Sync`, so they can't
+/// wander off to another one.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Symbol(u32, PhantomData<*const ()>);
+
+/// Every string interned so far. Nothing is ever taken out, so only intern
+/// things that are likely to come up again, like names.
+struct Interner {
+    symbols: HashMap<Rc<str>, Symbol>,
+    strings: Vec<Rc<str>>,
+}
+
+/// Names the interpreters look up themselves, which every interner starts
+/// out with so they have the same symbols on every thread.
+const PREDEFINED: [&str; 3] = ["init", "this", "super"];
+
+impl Interner {
+    fn new() -> Self {
+        let mut interner = Interner {
+            symbols: HashMap::new(),
+            strings: vec![],
+        };
+        for s in PREDEFINED.iter() {
+            interner.intern(s);
+        }
+        interner
+    }
+
+    fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(s) {
+            return symbol;
+        }
+
+        let symbol = Symbol(self.strings.len() as u32, PhantomData);
+        let s: Rc<str> = Rc::from(s);
+        self.strings.push(Rc::clone(&s));
+        self.symbols.insert(s, symbol);
+        symbol
+    }
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::new());
+}
+
+impl Symbol {
+    /// `init`, the name of a class's initializer.
+    pub const INIT: Symbol = Symbol(0, PhantomData);
+    /// `this`.
+    pub const THIS: Symbol = Symbol(1, PhantomData);
+    /// `super`.
+    pub const SUPER: Symbol = Symbol(2, PhantomData);
+
+    pub fn intern(s: &str) -> Symbol {
+        INTERNER.with(|interner| interner.borrow_mut().intern(s))
+    }
+
+    /// The interned string itself. Every call shares the same allocation.
+    pub fn as_str(self) -> Rc<str> {
+        INTERNER.with(|interner| Rc::clone(&interner.borrow().strings[self.0 as usize]))
+    }
+
+    /// Borrow the interned string, without even bumping a reference count.
+    pub fn with_str<R, F: FnOnce(&str) -> R>(self, f: F) -> R {
+        INTERNER.with(|interner| f(&interner.borrow().strings[self.0 as usize]))
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.with_str(|s| s == other)
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.with_str(|s| write!(f, "{}", s))
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.with_str(|s| write!(f, "Symbol({:?})", s))
+    }
+}
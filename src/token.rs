@@ -1,48 +1,138 @@
-use crate::{literal::Value, token_type::TokenType};
-use std::fmt::Debug;
+use crate::{literal::Value, symbol::Symbol, token_type::TokenType};
+use std::{
+    fmt::{Debug, Display},
+    rc::Rc,
+};
+
+/// A range of bytes in the source code.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Span {
+    /// Byte offset of the first byte in the range.
+    pub start: usize,
+    /// Byte offset just past the last byte in the range.
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// The smallest span that covers both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// The source code text of a token. Identifiers and keywords are interned,
+/// since the same few names come up over and over and everything is looked
+/// up by them. Anything else, like a number or a string, is kept just as it
+/// was written, so a program full of one-off literals doesn't fill up the
+/// interner.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Lexeme {
+    Name(Symbol),
+    Text(Rc<str>),
+}
+
+impl Lexeme {
+    /// The lexeme for a token of kind `kind` written as `text`.
+    pub fn new(kind: TokenType, text: &str) -> Self {
+        if kind.is_name() {
+            Lexeme::Name(Symbol::intern(text))
+        } else {
+            Lexeme::Text(Rc::from(text))
+        }
+    }
+
+    /// Borrow the text, however it's stored.
+    pub fn with_str<R, F: FnOnce(&str) -> R>(&self, f: F) -> R {
+        match self {
+            Lexeme::Name(name) => name.with_str(f),
+            Lexeme::Text(text) => f(text),
+        }
+    }
+}
+
+impl PartialEq<str> for Lexeme {
+    fn eq(&self, other: &str) -> bool {
+        self.with_str(|s| s == other)
+    }
+}
+
+impl PartialEq<&str> for Lexeme {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Display for Lexeme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.with_str(|s| write!(f, "{}", s))
+    }
+}
 
 /// A single token, taken straight out of the source code by the Scanner.
-#[derive(Debug)]
+/// Cloning one is cheap: names are interned and any other text is shared.
+#[derive(Debug, Clone)]
 pub struct Token {
     /// The kind of token that this is.
     pub kind: TokenType,
     /// The source code text that corresponds to this Token.
-    pub lexeme: String,
+    pub lexeme: Lexeme,
     /// Literals are anything printable. Could be a numeric value or the contents of a string.
     pub literal: Value,
-    /// The line that this token was found on in the source code
+    /// The line that this token was found on in the source code. Strings that
+    /// span several lines count as being on the last one.
     pub line: usize,
-}
-
-// yes, they're copyable! it might cost you precious CPU cycles though...
-impl Clone for Token {
-    fn clone(&self) -> Self {
-        Self {
-            kind: self.kind,
-            // possibly expensive for long string tokens and the like
-            lexeme: self.lexeme.clone(),
-            literal: self.literal.clone(),
-            line: self.line,
-        }
-    }
+    /// The column (in chars, starting at 1) of the token's first character.
+    pub column: usize,
+    /// Exactly where in the source code the lexeme came from.
+    pub span: Span,
 }
 
 impl Token {
-    pub fn new(kind: TokenType, lexeme: String, literal: Value, line: usize) -> Self {
+    pub fn new(
+        kind: TokenType,
+        lexeme: Lexeme,
+        literal: Value,
+        line: usize,
+        column: usize,
+        span: Span,
+    ) -> Self {
         Self {
             kind,
             lexeme,
             literal,
             line,
+            column,
+            span,
+        }
+    }
+
+    /// The name an identifier or keyword stands for. Panics for any other
+    /// token, but the parser only ever puts names where names go.
+    pub fn name(&self) -> Symbol {
+        match &self.lexeme {
+            Lexeme::Name(name) => *name,
+            Lexeme::Text(text) => panic!("{} {:?} isn't a name", self.kind, text),
         }
     }
 }
 
-impl ToString for Token {
-    fn to_string(&self) -> String {
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self.literal {
-            Value::Nil => format!("{} {}", self.kind, self.lexeme),
-            _ => format!("{} {} {}", self.kind, self.lexeme, self.literal),
+            Value::Nil => write!(f, "{} {}", self.kind, self.lexeme),
+            _ => write!(f, "{} {} {}", self.kind, self.lexeme, self.literal),
         }
     }
 }
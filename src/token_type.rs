@@ -3,12 +3,16 @@ use std::fmt::Display;
 #[allow(non_camel_case_types)]
 #[allow(dead_code)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u8)]
 pub enum TokenType {
     // Single-character tokens.
     LEFT_PAREN,
     RIGHT_PAREN,
     LEFT_BRACE,
     RIGHT_BRACE,
+    LEFT_BRACKET,
+    RIGHT_BRACKET,
+    COLON,
     COMMA,
     DOT,
     MINUS,
@@ -30,6 +34,9 @@ pub enum TokenType {
     // Literals.
     IDENTIFIER,
     STRING,
+    /// The text of a string up to a `${`. The expression inside comes next,
+    /// then more of the string as another `INTERPOLATION` or a `STRING`.
+    INTERPOLATION,
     NUMBER,
 
     // Keywords.
@@ -53,6 +60,65 @@ pub enum TokenType {
     EOF,
 }
 
+impl TokenType {
+    const ALL: [TokenType; 43] = [
+        TokenType::LEFT_PAREN,
+        TokenType::RIGHT_PAREN,
+        TokenType::LEFT_BRACE,
+        TokenType::RIGHT_BRACE,
+        TokenType::LEFT_BRACKET,
+        TokenType::RIGHT_BRACKET,
+        TokenType::COLON,
+        TokenType::COMMA,
+        TokenType::DOT,
+        TokenType::MINUS,
+        TokenType::PLUS,
+        TokenType::SEMICOLON,
+        TokenType::SLASH,
+        TokenType::STAR,
+        TokenType::BANG,
+        TokenType::BANG_EQUAL,
+        TokenType::EQUAL,
+        TokenType::EQUAL_EQUAL,
+        TokenType::GREATER,
+        TokenType::GREATER_EQUAL,
+        TokenType::LESS,
+        TokenType::LESS_EQUAL,
+        TokenType::IDENTIFIER,
+        TokenType::STRING,
+        TokenType::INTERPOLATION,
+        TokenType::NUMBER,
+        TokenType::AND,
+        TokenType::CLASS,
+        TokenType::ELSE,
+        TokenType::FALSE,
+        TokenType::FUN,
+        TokenType::FOR,
+        TokenType::IF,
+        TokenType::NIL,
+        TokenType::OR,
+        TokenType::PRINT,
+        TokenType::RETURN,
+        TokenType::SUPER,
+        TokenType::THIS,
+        TokenType::TRUE,
+        TokenType::VAR,
+        TokenType::WHILE,
+        TokenType::EOF,
+    ];
+
+    /// The kind of token a byte stands for, if it stands for one at all.
+    pub fn from_byte(byte: u8) -> Option<TokenType> {
+        TokenType::ALL.get(byte as usize).copied()
+    }
+
+    /// Whether tokens of this kind are names: identifiers and keywords.
+    pub fn is_name(self) -> bool {
+        let keywords = TokenType::AND as u8..=TokenType::WHILE as u8;
+        self == TokenType::IDENTIFIER || keywords.contains(&(self as u8))
+    }
+}
+
 impl Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
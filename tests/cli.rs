@@ -0,0 +1,110 @@
+//! What the `jlox` binary itself prints and exits with, since the conformance
+//! suite only looks at the library's errors.
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Write `source` to a file called `name` and hand back its path.
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn jlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jlox"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap()
+}
+
+/// Check that running `jlox` with `args` prints `stdout` and `stderr` and
+/// exits with `code`.
+fn check(args: &[&str], stdout: &str, stderr: &str, code: i32) {
+    let output = jlox(args);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        stdout,
+        "{:?}",
+        args
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        stderr,
+        "{:?}",
+        args
+    );
+    assert_eq!(output.status.code(), Some(code), "{:?}", args);
+}
+
+#[test]
+fn compile_errors() {
+    let path = script("compile_errors.lox", "print 1;\nprint 1 +;\nprint (;\n");
+    let path = path.to_str().unwrap();
+    let stderr = format!(
+        "\
+error: Expect expression.
+ --> {path}:2:10
+  |
+2 | print 1 +;
+  |          ^
+error: Expect expression.
+ --> {path}:3:8
+  |
+3 | print (;
+  |        ^
+",
+        path = path
+    );
+    check(&[path], "", &stderr, 65);
+    check(&["--vm", path], "", &stderr, 65);
+}
+
+#[test]
+fn runtime_errors() {
+    let path = script("runtime_errors.lox", "print 1;\nprint nil + 1;\n");
+    let path = path.to_str().unwrap();
+    let stderr = format!(
+        "\
+error: Operands must be two numbers or two strings.
+ --> {path}:2:11
+  |
+2 | print nil + 1;
+  |           ^
+  |       --- this is nil
+  |             - this is a number
+",
+        path = path
+    );
+    check(&[path], "1\n", &stderr, 70);
+    check(&["--vm", path], "1\n", &stderr, 70);
+
+    // without the source there's nothing to show but the message
+    let compiled = path.replace(".lox", ".loxc");
+    check(&["compile", path, &compiled], "", "", 0);
+    check(
+        &[&compiled],
+        "1\n",
+        "Operands must be two numbers or two strings.\n[line 2]\n",
+        70,
+    );
+}
+
+#[test]
+fn bad_arguments() {
+    let output = jlox(&["--nope"]);
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Usage: jlox"));
+    assert_eq!(output.status.code(), Some(64));
+}
+
+#[test]
+fn missing_script() {
+    let output = jlox(&["no/such/script.lox"]);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("no/such/script.lox: "));
+    assert_eq!(output.status.code(), Some(1));
+}
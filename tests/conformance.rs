@@ -0,0 +1,303 @@
+//! Runs the Lox programs under `tests/lox` on both backends, and once more
+//! through a `.loxc` file, and checks what they do against the expectations
+//! written in their comments, in the same format as the official Crafting
+//! Interpreters test suite:
+//!
+//! - `// expect: output` is a line the program should print.
+//! - `// expect runtime error: message` means the program should stop with
+//!   that runtime error on this line, exiting with 70.
+//! - `// Error at 'x': message` is a compile error expected on this line, and
+//!   `// [line N] Error...` is one expected on line N. Either way it should
+//!   exit with 65, without running anything.
+//!
+//! Expectations tagged `[c line N]` only apply to clox, so they're skipped.
+//! Ones tagged `[vm line N]` only apply to the bytecode VM, for limits that
+//! come from how it encodes things; the tree-walker should run those programs
+//! as if they weren't there.
+//!
+//! Test programs are kept as they were written, even the few that a later
+//! language extension changes the meaning of. Those are listed in
+//! [`SUPERSEDED`] and skipped, and the tests for what they do now live
+//! elsewhere.
+
+use jlox::{bytecode::loxc, Backend, Lox, LoxError, Value, STACK_SIZE};
+use std::{
+    cell::RefCell,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+};
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+
+/// Test programs that mean something else now, and why.
+const SUPERSEDED: &[(&str, &str)] = &[
+    (
+        "for/statement_initializer.lox",
+        "`{}` is an empty map, so the error is at runtime; see map/for_initializer.lox",
+    ),
+    (
+        "for/statement_condition.lox",
+        "`{}` is an empty map, so the loop never ends; see map/for_condition.lox",
+    ),
+    (
+        "for/statement_increment.lox",
+        "`{}` is an empty map, so the loop never ends; see map/for_increment.lox",
+    ),
+    (
+        "list/index_non_list.lox",
+        "maps can be indexed too; see map/index_non_indexable.lox",
+    ),
+    (
+        "list/len_of_number.lox",
+        "maps have a length too; see map/len_of_number.lox",
+    ),
+];
+
+/// What a test program is supposed to do.
+#[derive(Debug, Default)]
+struct Expectations {
+    output: Vec<String>,
+    compile_errors: Vec<String>,
+    runtime_error: Option<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str, backend: Backend) -> Self {
+        let mut expected = Self::default();
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            if let Some(index) = line.find(EXPECT) {
+                expected
+                    .output
+                    .push(line[index + EXPECT.len()..].to_string());
+            } else if let Some(index) = line.find(EXPECT_RUNTIME_ERROR) {
+                let message = &line[index + EXPECT_RUNTIME_ERROR.len()..];
+                expected.runtime_error = Some(format!("{}\n[line {}]", message, line_number));
+            } else if let Some(index) = line.find("// Error") {
+                expected.compile_errors.push(format!(
+                    "[line {}] {}",
+                    line_number,
+                    &line[index + 3..]
+                ));
+            } else if let Some(index) = line.find("// [line ") {
+                expected.compile_errors.push(line[index + 3..].to_string());
+            } else if let Some(index) = line.find("// [java line ") {
+                expected
+                    .compile_errors
+                    .push(format!("[{}", &line[index + "// [java ".len()..]));
+            } else if let Some(index) = line.find("// [vm line ") {
+                if backend == Backend::Bytecode {
+                    expected
+                        .compile_errors
+                        .push(format!("[{}", &line[index + "// [vm ".len()..]));
+                }
+            }
+        }
+        expected.compile_errors.sort();
+        expected
+    }
+
+    fn exit_code(&self) -> u8 {
+        if !self.compile_errors.is_empty() {
+            65
+        } else if self.runtime_error.is_some() {
+            70
+        } else {
+            0
+        }
+    }
+}
+
+/// The ways a test program gets run. They should all behave the same.
+#[derive(Debug, Copy, Clone)]
+enum Mode {
+    TreeWalk,
+    Bytecode,
+    /// Compiled, saved as `.loxc` and loaded back before running on the VM.
+    Loxc,
+}
+
+impl Mode {
+    fn backend(self) -> Backend {
+        match self {
+            Mode::TreeWalk => Backend::TreeWalk,
+            Mode::Bytecode | Mode::Loxc => Backend::Bytecode,
+        }
+    }
+
+    fn eval(self, lox: &mut Lox, source: &str) -> Result<Value, LoxError> {
+        match self {
+            Mode::TreeWalk | Mode::Bytecode => lox.eval(source),
+            Mode::Loxc => {
+                let script = lox.compile(source)?;
+                let bytes = loxc::serialize(&script)?;
+                lox.run_compiled(loxc::deserialize(&bytes)?)
+            }
+        }
+    }
+}
+
+/// Run one test program, describing everything it got wrong.
+fn run(path: &Path, mode: Mode) -> Vec<String> {
+    let source = fs::read_to_string(path).unwrap();
+    let expected = Expectations::parse(&source, mode.backend());
+
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut lox = Lox::with_backend(mode.backend());
+    lox.set_output(output.clone());
+    let result = mode.eval(&mut lox, &source);
+
+    let mut failures = vec![];
+    let (exit_code, mut errors) = match &result {
+        Ok(_) => (0, vec![]),
+        Err(error) => (
+            error.exit_code(),
+            error.to_string().lines().map(String::from).collect(),
+        ),
+    };
+
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    let output: Vec<_> = output.lines().collect();
+    if output != expected.output {
+        failures.push(format!(
+            "expected output {:?}, got {:?}",
+            expected.output, output
+        ));
+    }
+
+    if exit_code != expected.exit_code() {
+        failures.push(format!(
+            "expected exit code {}, got {}",
+            expected.exit_code(),
+            exit_code
+        ));
+    }
+
+    if let Some(runtime_error) = &expected.runtime_error {
+        let got = result.as_ref().err().map(ToString::to_string);
+        if got.as_ref() != Some(runtime_error) {
+            failures.push(format!(
+                "expected runtime error {:?}, got {:?}",
+                runtime_error, got
+            ));
+        }
+    } else if exit_code == 65 || !expected.compile_errors.is_empty() {
+        errors.sort();
+        if errors != expected.compile_errors {
+            failures.push(format!(
+                "expected errors {:?}, got {:?}",
+                expected.compile_errors, errors
+            ));
+        }
+    } else if let Err(error) = &result {
+        failures.push(format!("unexpected error {:?}", error.to_string()));
+    }
+
+    failures
+}
+
+/// Every `.lox` file directly inside `dir`, in a stable order.
+fn lox_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lox"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn run_suite(dir: &'static str) {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || check_suite(dir))
+        .unwrap()
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+}
+
+fn check_suite(dir: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    let dir = root.join(dir);
+    let files = lox_files(&dir);
+    assert!(!files.is_empty(), "no tests in {}", dir.display());
+
+    let mut failures = vec![];
+    for path in &files {
+        let superseded = SUPERSEDED.iter().any(|&(name, _)| root.join(name) == *path);
+        if superseded {
+            continue;
+        }
+        for &mode in &[Mode::TreeWalk, Mode::Bytecode, Mode::Loxc] {
+            for failure in run(path, mode) {
+                failures.push(format!("{} ({:?}): {}", path.display(), mode, failure));
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!(
+            "{} failures across {} tests:\n{}",
+            failures.len(),
+            files.len(),
+            failures.join("\n")
+        );
+    }
+}
+
+#[test]
+fn superseded_tests_exist() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+    for (name, _) in SUPERSEDED {
+        assert!(root.join(name).is_file(), "{} doesn't exist", name);
+    }
+}
+
+macro_rules! suites {
+    ($($name:ident => $dir:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                run_suite($dir);
+            }
+        )*
+    };
+}
+
+suites! {
+    top_level => "",
+    assignment => "assignment",
+    block => "block",
+    bool => "bool",
+    call => "call",
+    class => "class",
+    closure => "closure",
+    comments => "comments",
+    constructor => "constructor",
+    extensions => "extensions",
+    field => "field",
+    for_loop => "for",
+    function => "function",
+    if_statement => "if",
+    inheritance => "inheritance",
+    limit => "limit",
+    list => "list",
+    logical_operator => "logical_operator",
+    map => "map",
+    method => "method",
+    native => "native",
+    nil => "nil",
+    number => "number",
+    operator => "operator",
+    print => "print",
+    regression => "regression",
+    return_statement => "return",
+    string => "string",
+    super_class => "super",
+    this => "this",
+    variable => "variable",
+    while_loop => "while",
+}
@@ -0,0 +1,342 @@
+//! The `.loxc` format: compiled programs should come back out of it exactly as
+//! they went in, and broken files should be turned away before the VM gets
+//! anywhere near them.
+
+use jlox::{
+    bytecode::{
+        chunk::{OpCode, Position},
+        loxc,
+        object::{Function, Object},
+    },
+    symbol::Symbol,
+    Lox, Value,
+};
+use std::{cell::RefCell, rc::Rc};
+
+const PROGRAM: &str = r#"
+class Counter {
+  init(start) { this.count = start; }
+  bump() {
+    this.count = this.count + 1;
+    return this.count;
+  }
+}
+
+fun makeAdder(n) {
+  fun add(x) { return x + n; }
+  return add;
+}
+
+var counter = Counter(10);
+counter.bump();
+var add = makeAdder(counter.bump());
+var list = [1, "two", nil];
+var map = {"a": add(1), true: list};
+for (var i = 0; i < 3; i = i + 1) {
+  push(list, i * 2);
+}
+print "${map} and ${len(list)}";
+print -add(0.5);
+"#;
+
+/// Everything `run` prints, and what it hands back.
+fn printed<F>(run: F) -> (String, String)
+where
+    F: FnOnce(&mut Lox) -> Value,
+{
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let mut lox = Lox::new();
+    lox.set_output(output.clone());
+    let value = run(&mut lox);
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    (output, value.to_string())
+}
+
+#[test]
+fn round_trip() {
+    let script = Lox::new().compile(PROGRAM).unwrap();
+    let bytes = loxc::serialize(&script).unwrap();
+    let loaded = loxc::deserialize(&bytes).unwrap();
+    assert_eq!(loxc::serialize(&loaded).unwrap(), bytes);
+
+    let expected = printed(|lox| lox.eval(PROGRAM).unwrap());
+    let got = printed(|lox| lox.run_compiled(loaded).unwrap());
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn runtime_errors_survive_a_round_trip() {
+    let script = Lox::new().compile("var x = 1;\nprint x + nil;").unwrap();
+    let loaded = loxc::deserialize(&loxc::serialize(&script).unwrap()).unwrap();
+    let error = Lox::new().run_compiled(loaded).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Operands must be two numbers or two strings.\n[line 2]"
+    );
+    assert_eq!(error.diagnostics()[0].notes.labels.len(), 2);
+}
+
+/// A script made of `code`, with `constants` in its constant pool and a
+/// single name, `A`.
+fn script(constants: Vec<Value>, code: &[u8]) -> Function {
+    let mut script = Function::new("");
+    script.chunk.add_name(Symbol::intern("A"));
+    for &byte in code {
+        script.chunk.write(byte, Position::default());
+    }
+    for constant in constants {
+        script.chunk.add_constant(constant);
+    }
+    script
+}
+
+/// Check that loading `script` fails because of `problem`.
+fn rejects_script(script: &Function, problem: &str) {
+    let bytes = loxc::serialize(script).unwrap();
+    match loxc::deserialize(&bytes) {
+        Ok(_) => panic!("{:?} was loaded, but {}", script.chunk.code, problem),
+        Err(error) => assert!(
+            error.to_string().contains(problem),
+            "{:?} was rejected with {:?}, not because {}",
+            script.chunk.code,
+            error.to_string(),
+            problem
+        ),
+    }
+}
+
+/// Serialize a script made of `code`, and check that loading it fails
+/// because of `problem`.
+fn rejects(code: &[u8], problem: &str) {
+    rejects_script(&script(vec![Value::Number(1.0)], code), problem);
+}
+
+/// Load a script made of `code` and `constants`, and check that running it
+/// fails with a runtime error because of `problem`.
+fn fails(constants: Vec<Value>, code: &[u8], problem: &str) {
+    let bytes = loxc::serialize(&script(constants, code)).unwrap();
+    let loaded = loxc::deserialize(&bytes).unwrap();
+    let error = Lox::new().run_compiled(loaded).unwrap_err();
+    assert!(
+        error.to_string().starts_with(problem),
+        "{:?} failed with {:?}, not because {}",
+        code,
+        error.to_string(),
+        problem
+    );
+}
+
+#[test]
+fn rejects_bad_headers() {
+    let script = Lox::new().compile("print 1;").unwrap();
+    let bytes = loxc::serialize(&script).unwrap();
+
+    let mut wrong_magic = bytes.clone();
+    wrong_magic[0] = b'J';
+    assert!(loxc::deserialize(&wrong_magic).is_err());
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[4] = wrong_version[4].wrapping_add(1);
+    assert!(loxc::deserialize(&wrong_version).is_err());
+
+    for len in 0..bytes.len() {
+        assert!(loxc::deserialize(&bytes[..len]).is_err(), "cut at {}", len);
+    }
+}
+
+#[test]
+fn rejects_unknown_opcodes() {
+    rejects(&[0xff, OpCode::Return as u8], "unknown opcode");
+}
+
+#[test]
+fn rejects_missing_operands() {
+    rejects(
+        &[OpCode::Nil as u8, OpCode::Constant as u8, 0],
+        "missing operands",
+    );
+}
+
+#[test]
+fn rejects_missing_constants() {
+    rejects(
+        &[OpCode::Constant as u8, 0, 7, OpCode::Return as u8],
+        "doesn't exist",
+    );
+}
+
+#[test]
+fn rejects_missing_locals() {
+    rejects(
+        &[OpCode::GetLocal as u8, 5, OpCode::Return as u8],
+        "slot that doesn't exist",
+    );
+}
+
+#[test]
+fn rejects_missing_upvalues() {
+    rejects(
+        &[OpCode::GetUpvalue as u8, 0, OpCode::Return as u8],
+        "upvalue that doesn't exist",
+    );
+}
+
+#[test]
+fn rejects_stack_underflow() {
+    rejects(
+        &[OpCode::Nil as u8, OpCode::Add as u8, OpCode::Return as u8],
+        "more off the stack than there is",
+    );
+    rejects(
+        &[OpCode::BuildList as u8, 0, 3, OpCode::Return as u8],
+        "more off the stack than there is",
+    );
+}
+
+#[test]
+fn rejects_stack_depth_that_depends_on_the_path() {
+    // pushes another nil every time around the loop
+    rejects(
+        &[OpCode::Nil as u8, OpCode::Loop as u8, 0, 4],
+        "depends on how it got there",
+    );
+}
+
+#[test]
+fn rejects_jumps_into_operands() {
+    rejects(
+        &[
+            OpCode::Jump as u8,
+            0,
+            1,
+            OpCode::GetLocal as u8,
+            0,
+            OpCode::Return as u8,
+        ],
+        "middle of nowhere",
+    );
+}
+
+#[test]
+fn rejects_running_off_the_end() {
+    rejects(&[OpCode::Nil as u8, OpCode::Pop as u8], "runs off the end");
+}
+
+#[test]
+fn rejects_missing_names() {
+    for &op in &[
+        OpCode::GetGlobal,
+        OpCode::DefineGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetProperty,
+        OpCode::SetProperty,
+        OpCode::GetSuper,
+        OpCode::Class,
+        OpCode::Method,
+    ] {
+        rejects(
+            &[op as u8, 0, 1, OpCode::Return as u8],
+            "name 1 at 0 doesn't exist",
+        );
+    }
+}
+
+#[test]
+fn rejects_closures_of_anything_but_functions() {
+    let script = script(
+        vec![Value::String("f".into())],
+        &[OpCode::Closure as u8, 0, 0, OpCode::Return as u8],
+    );
+    rejects_script(&script, "needs a function");
+}
+
+#[test]
+fn rejects_scripts_with_arguments_or_upvalues() {
+    let mut takes_arguments = script(vec![], &[OpCode::Nil as u8, OpCode::Return as u8]);
+    takes_arguments.arity = 1;
+    rejects_script(&takes_arguments, "can't take arguments");
+
+    let mut captures = script(vec![], &[OpCode::GetUpvalue as u8, 0, OpCode::Return as u8]);
+    captures.upvalue_count = 1;
+    rejects_script(&captures, "capture variables");
+}
+
+#[test]
+fn values_of_the_wrong_type_are_runtime_errors() {
+    let method = || {
+        let method = script(vec![], &[OpCode::Nil as u8, OpCode::Return as u8]);
+        Value::Object(Object::Function(Rc::new(method)))
+    };
+
+    // a number for the superclass
+    fails(
+        vec![Value::Number(1.0)],
+        &[
+            OpCode::Nil as u8,
+            OpCode::Class as u8,
+            0,
+            0,
+            OpCode::Inherit as u8,
+            OpCode::Return as u8,
+        ],
+        "Superclass must be a class.",
+    );
+    // a number for the subclass
+    fails(
+        vec![Value::Number(1.0)],
+        &[
+            OpCode::Class as u8,
+            0,
+            0,
+            OpCode::Nil as u8,
+            OpCode::Inherit as u8,
+            OpCode::Return as u8,
+        ],
+        "Only classes can inherit.",
+    );
+    // a number for the method
+    fails(
+        vec![Value::Number(1.0)],
+        &[
+            OpCode::Class as u8,
+            0,
+            0,
+            OpCode::Constant as u8,
+            0,
+            0,
+            OpCode::Method as u8,
+            0,
+            0,
+            OpCode::Return as u8,
+        ],
+        "Methods must be functions.",
+    );
+    // nil for the class the method goes on
+    fails(
+        vec![method()],
+        &[
+            OpCode::Nil as u8,
+            OpCode::Closure as u8,
+            0,
+            0,
+            OpCode::Method as u8,
+            0,
+            0,
+            OpCode::Return as u8,
+        ],
+        "Only classes have methods.",
+    );
+    // nil for the superclass 'super' looks in
+    fails(
+        vec![Value::Number(1.0)],
+        &[
+            OpCode::Nil as u8,
+            OpCode::Nil as u8,
+            OpCode::GetSuper as u8,
+            0,
+            0,
+            OpCode::Return as u8,
+        ],
+        "Superclass must be a class.",
+    );
+}
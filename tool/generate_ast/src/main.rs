@@ -1,12 +1,11 @@
-use exit::Exit;
-use std::{env, fs::File, io::BufWriter, path::PathBuf};
+use std::{env, fs::File, io::BufWriter, path::PathBuf, process::ExitCode};
 use subprocess::{Popen, PopenConfig, Redirection};
 
-fn main() -> Exit<i8> {
+fn main() -> ExitCode {
     let args: Vec<_> = env::args().skip(1).collect();
     if args.len() != 1 {
         eprintln!("Usage: generate_ast <output directory>");
-        return Exit::Err(64);
+        return ExitCode::from(64);
     }
 
     let output_dir = &args[0];
@@ -14,28 +13,49 @@ fn main() -> Exit<i8> {
         output_dir,
         "Expr",
         vec![
-            "Assign   : Token, Expr",
-            "Binary   : Expr, Token, Expr",
-            "Grouping : Expr",
-            "Literal  : Value",
-            "Unary    : Token, Expr",
-            "Variable : Token",
+            "Assign        : Token, Expr, Cell<Option<usize>>",
+            "Binary        : Expr, Token, Expr",
+            "Call          : Expr, Token, Vec<Expr>",
+            "Get           : Expr, Token",
+            "Grouping      : Expr",
+            "Index         : Expr, Token, Expr",
+            "Interpolation : Token, Vec<Expr>",
+            "List          : Token, Vec<Expr>, Token",
+            "Literal       : Token, Value",
+            "Logical       : Expr, Token, Expr",
+            "Map           : Token, Vec<Expr>, Vec<Expr>, Token",
+            "Set           : Expr, Token, Expr",
+            "SetIndex      : Expr, Token, Expr, Expr",
+            "Super         : Token, Token, Cell<Option<usize>>",
+            "This          : Token, Cell<Option<usize>>",
+            "Unary         : Token, Expr",
+            "Variable      : Token, Cell<Option<usize>>",
         ],
-        vec!["crate::token::Token", "crate::literal::Value"],
+        vec!["crate::token::Token", "crate::literal::Value", "std::cell::Cell"],
     );
 
     define_ast(
         output_dir,
         "Stmt",
         vec![
+            "Block      : Vec<Stmt>",
+            "Class      : Token, Token, Option<Expr>, Vec<Stmt>",
             "Expression : Expr",
-            "Print      : Expr",
-            "Var        : Token, Option<Expr>",
+            "Function   : Option<Token>, Token, Vec<Token>, Rc<Vec<Stmt>>",
+            "If         : Token, Expr, Stmt, Option<Stmt>",
+            "Print      : Token, Expr",
+            "Return     : Token, Option<Expr>",
+            "Var        : Token, Token, Option<Expr>",
+            "While      : Token, Expr, Stmt",
+        ],
+        vec![
+            "crate::ast::expr::Expr",
+            "crate::token::Token",
+            "std::rc::Rc",
         ],
-        vec!["crate::ast::expr::Expr", "crate::token::Token"],
     );
 
-    Exit::Ok
+    ExitCode::SUCCESS
 }
 
 #[derive(Debug)]
@@ -61,6 +81,25 @@ fn gen_uses(uses: Vec<&'static str>) -> String {
     s
 }
 
+/// Fields that hold the type being defined have to be boxed, or it'd be infinitely sized.
+fn stored_type(field: &str, base: &str) -> String {
+    if field == base {
+        format!("Box<{}>", field)
+    } else if field == format!("Option<{}>", base) {
+        format!("Option<Box<{}>>", base)
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Visitor arguments take slices rather than `&Vec<_>`.
+fn borrowed_type(field: &str) -> String {
+    match field.strip_prefix("Vec<") {
+        Some(inner) => format!("[{}]", inner.trim_end_matches('>')),
+        None => field.to_owned(),
+    }
+}
+
 fn define_ast(
     output_dir: &str,
     base_name: &str,
@@ -95,7 +134,15 @@ fn define_ast(
                     typed_a_list: fields
                         .iter()
                         .enumerate()
-                        .map(|(i, t)| format!("a{}: &{},", i, t))
+                        .map(|(i, t)| {
+                            // a boxed field derefs to a plain reference, but a boxed option doesn't
+                            let t = if t.starts_with("Option<") {
+                                stored_type(t, base_name)
+                            } else {
+                                t.to_owned()
+                            };
+                            format!("a{}: &{},", i, borrowed_type(&t))
+                        })
                         .collect(),
                     fields,
                 }
@@ -107,12 +154,7 @@ fn define_ast(
     for ty in &type_list.types {
         contents.push_str(&format!("{}(", ty.name));
         for field in &ty.fields {
-            if field == &type_list.base {
-                use std::fmt::Write;
-                write!(contents, "Box<{}>,", field).unwrap();
-            } else {
-                contents.push_str(&format!("{},", field));
-            }
+            contents.push_str(&format!("{},", stored_type(field, &type_list.base)));
         }
         contents.push_str("),");
     }